@@ -0,0 +1,218 @@
+use std::fmt::Debug;
+use crate::visit::{Child, Fold, Visitor, VisitorMut};
+
+/// Defines an AST enum or struct and implements `flexar::visit::Walk` for it
+/// - Fields that are `Node`s (or `Box`es, `Vec`s, `Box<[_]>`s and `Option`s of them) are walked in order, and every other field is skipped
+/// ```ignore
+/// flexar::ast! {
+///     #[derive(Debug)]
+///     pub enum Expr {
+///         Plus(Node<Factor>, Box<Node<Expr>>),
+///         Call { name: String, args: Vec<Node<Expr>> },
+///         Int(u64),
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! ast {
+    ($(#[$meta:meta])* $vis:vis enum $name:ident { $($(#[$vmeta:meta])* $variant:ident $(($($tuple:ty),* $(,)?))? $({$($(#[$fmeta:meta])* $field:ident: $fty:ty),* $(,)?})?),* $(,)? }) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($(#[$vmeta])* $variant $(($($tuple),*))? $({$($(#[$fmeta])* $field: $fty),*})?,)*
+        }
+
+        #[allow(irrefutable_let_patterns, unreachable_code, unused_imports)]
+        impl $crate::visit::Walk for $name {
+            fn walk<V: $crate::visit::Visitor>(&self, visitor: &mut V) {
+                use $crate::ast_macro::{Field, WalkField, WalkLeaf};
+                $($crate::ast!(@bind walk self visitor $name $variant [] $(($($tuple),*))? $({$($field),*})?);)*
+            }
+
+            fn walk_mut<V: $crate::visit::VisitorMut>(&mut self, visitor: &mut V) {
+                use $crate::ast_macro::{Field, WalkFieldMut, WalkLeafMut};
+                $($crate::ast!(@bind walk_mut self visitor $name $variant [] $(($($tuple),*))? $({$($field),*})?);)*
+            }
+
+            fn fold_children<F: $crate::visit::Fold>(self, folder: &mut F) -> Self {
+                use $crate::ast_macro::{Field, FoldField, FoldLeaf};
+                $($crate::ast!(@bind fold self folder $name $variant [] $(($($tuple),*))? $({$($field),*})?);)*
+                self
+            }
+
+            fn label(&self) -> String {
+                use $crate::ast_macro::{Field, LabelField, LabelLeaf};
+                $($crate::ast!(@bind label self label $name $variant [] $(($($tuple),*))? $({$($field),*})?);)*
+                format!("{self:?}")
+            }
+        }
+    };
+
+    ($(#[$meta:meta])* $vis:vis struct $name:ident { $($(#[$fmeta:meta])* $fvis:vis $field:ident: $fty:ty),* $(,)? }) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$fmeta])* $fvis $field: $fty,)*
+        }
+
+        #[allow(unused_imports)]
+        impl $crate::visit::Walk for $name {
+            fn walk<V: $crate::visit::Visitor>(&self, visitor: &mut V) {
+                use $crate::ast_macro::{Field, WalkField, WalkLeaf};
+                $((&Field(&self.$field)).walk_field(visitor);)*
+            }
+
+            fn walk_mut<V: $crate::visit::VisitorMut>(&mut self, visitor: &mut V) {
+                use $crate::ast_macro::{Field, WalkFieldMut, WalkLeafMut};
+                $((&mut Field(&mut self.$field)).walk_field_mut(visitor);)*
+            }
+
+            fn fold_children<F: $crate::visit::Fold>(self, folder: &mut F) -> Self {
+                use $crate::ast_macro::{Field, FoldField, FoldLeaf};
+                Self { $($field: (&mut Field(Some(self.$field))).fold_field(folder),)* }
+            }
+
+            fn label(&self) -> String {
+                use $crate::ast_macro::{Field, LabelField, LabelLeaf};
+                let mut label = String::from(stringify!($name));
+                $(if let Some(x) = (&Field(&self.$field)).leaf_label() { label.push_str(concat!(" ", stringify!($field), ": ")); label.push_str(&x) })*
+                label
+            }
+        }
+    };
+
+    // Names the fields of tuple variants (each `field` is a different binding thanks to hygiene)
+    (@bind $op:ident $self:ident $v:ident $name:ident $variant:ident [$($bound:ident)*] ($head:ty $(, $tail:ty)*)) => {
+        $crate::ast!(@bind $op $self $v $name $variant [$($bound)* field] ($($tail),*))
+    };
+    (@bind $op:ident $self:ident $v:ident $name:ident $variant:ident [$($bound:ident)*] ()) => {
+        $crate::ast!(@emit $op $self $v $name $variant ($($bound),*))
+    };
+    (@bind $op:ident $self:ident $v:ident $name:ident $variant:ident [] {$($field:ident),*}) => {
+        $crate::ast!(@emit $op $self $v $name $variant {$($field),*})
+    };
+    (@bind $op:ident $self:ident $v:ident $name:ident $variant:ident []) => {};
+
+    (@emit walk $self:ident $v:ident $name:ident $variant:ident ($($field:ident),*)) => {
+        if let $name::$variant($($field),*) = $self { $((&Field($field)).walk_field($v);)* }
+    };
+    (@emit walk $self:ident $v:ident $name:ident $variant:ident {$($field:ident),*}) => {
+        if let $name::$variant { $($field),* } = $self { $((&Field($field)).walk_field($v);)* }
+    };
+    (@emit walk_mut $self:ident $v:ident $name:ident $variant:ident ($($field:ident),*)) => {
+        if let $name::$variant($($field),*) = $self { $((&mut Field($field)).walk_field_mut($v);)* }
+    };
+    (@emit walk_mut $self:ident $v:ident $name:ident $variant:ident {$($field:ident),*}) => {
+        if let $name::$variant { $($field),* } = $self { $((&mut Field($field)).walk_field_mut($v);)* }
+    };
+    (@emit fold $self:ident $v:ident $name:ident $variant:ident ($($field:ident),*)) => {
+        if let $name::$variant($($field),*) = $self { return $name::$variant($((&mut Field(Some($field))).fold_field($v)),*) }
+    };
+    (@emit label $self:ident $label:ident $name:ident $variant:ident ($($field:ident),*)) => {
+        if let $name::$variant($($field),*) = $self {
+            let mut $label = String::from(stringify!($variant));
+            $(if let Some(x) = (&Field($field)).leaf_label() { $label.push(' '); $label.push_str(&x) })*
+            return $label;
+        }
+    };
+    (@emit label $self:ident $label:ident $name:ident $variant:ident {$($field:ident),*}) => {
+        if let $name::$variant { $($field),* } = $self {
+            let mut $label = String::from(stringify!($variant));
+            $(if let Some(x) = (&Field($field)).leaf_label() { $label.push_str(concat!(" ", stringify!($field), ": ")); $label.push_str(&x) })*
+            return $label;
+        }
+    };
+    (@emit fold $self:ident $v:ident $name:ident $variant:ident {$($field:ident),*}) => {
+        if let $name::$variant { $($field),* } = $self { return $name::$variant { $($field: (&mut Field(Some($field))).fold_field($v)),* } }
+    };
+}
+
+/// Wraps a field so fields that can contain nodes are walked and every other field is skipped
+/// (the `...Field` traits are implemented for `Field` and the `...Leaf` ones for a reference to it, so method lookup prefers the former)
+#[doc(hidden)]
+pub struct Field<T>(pub T);
+
+#[doc(hidden)]
+pub trait WalkField {
+    fn walk_field<V: Visitor>(&self, visitor: &mut V);
+}
+
+impl<T: Child> WalkField for Field<&T> {
+    #[inline]
+    fn walk_field<V: Visitor>(&self, visitor: &mut V) {
+        self.0.visit_child(visitor)
+    }
+}
+
+#[doc(hidden)]
+pub trait WalkLeaf {
+    #[inline]
+    fn walk_field<V: Visitor>(&self, _: &mut V) {}
+}
+
+impl<T> WalkLeaf for &Field<T> {}
+
+#[doc(hidden)]
+pub trait WalkFieldMut {
+    fn walk_field_mut<V: VisitorMut>(&mut self, visitor: &mut V);
+}
+
+impl<T: Child> WalkFieldMut for Field<&mut T> {
+    #[inline]
+    fn walk_field_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
+        self.0.visit_child_mut(visitor)
+    }
+}
+
+#[doc(hidden)]
+pub trait WalkLeafMut {
+    #[inline]
+    fn walk_field_mut<V: VisitorMut>(&mut self, _: &mut V) {}
+}
+
+impl<T> WalkLeafMut for &mut Field<T> {}
+
+#[doc(hidden)]
+pub trait LabelField {
+    /// Fields that contain nodes aren't part of the label
+    #[inline]
+    fn leaf_label(&self) -> Option<String> {
+        None
+    }
+}
+
+impl<T: Child> LabelField for Field<&T> {}
+
+#[doc(hidden)]
+pub trait LabelLeaf {
+    fn leaf_label(&self) -> Option<String>;
+}
+
+impl<T: Debug> LabelLeaf for &Field<&T> {
+    #[inline]
+    fn leaf_label(&self) -> Option<String> {
+        Some(format!("{:?}", self.0))
+    }
+}
+
+#[doc(hidden)]
+pub trait FoldField<T> {
+    fn fold_field<F: Fold>(&mut self, folder: &mut F) -> T;
+}
+
+impl<T: Child> FoldField<T> for Field<Option<T>> {
+    #[inline]
+    fn fold_field<F: Fold>(&mut self, folder: &mut F) -> T {
+        self.0.take().expect("fields are only folded once").fold_child(folder)
+    }
+}
+
+#[doc(hidden)]
+pub trait FoldLeaf<T> {
+    fn fold_field<F: Fold>(&mut self, folder: &mut F) -> T;
+}
+
+impl<T> FoldLeaf<T> for &mut Field<Option<T>> {
+    #[inline]
+    fn fold_field<F: Fold>(&mut self, _: &mut F) -> T {
+        self.0.take().expect("fields are only folded once")
+    }
+}
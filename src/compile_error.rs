@@ -5,32 +5,58 @@ pub use compile_error_format::*;
 pub use crate::compilerr_fmt;
 pub use crate::compile_error;
 
-use std::error::Error;
+use std::{borrow::Cow, cell::{Cell, RefCell}, error::Error, panic::{self, AssertUnwindSafe}};
 use crate::cursor::Position;
 
+thread_local! {
+    /// The last error thrown on this thread in debug mode (so `flexar::testing` can catch it)
+    pub(crate) static THROWN: RefCell<Option<CompileError>> = const { RefCell::new(None) };
+    /// Whether `throw` is inside `CompileError::catch` on this thread
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
 /// Errors that occur during the compilation stage
+/// - The `id` and `error_type` are borrowed when they come from `compiler_error!` and owned when they're deserialised
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CompileError {
-    pub id: &'static str,
-    pub error_type: &'static str,
+    pub id: Cow<'static, str>,
+    pub error_type: Cow<'static, str>,
     pub msg: String,
     pub position: Position,
 }
 
 impl CompileError {
     #[inline]
-    pub fn new(id: &'static str, error_type: &'static str, msg: String, position: Position) -> Self {
-        CompileError { id, error_type, msg, position }
+    pub fn new(id: impl Into<Cow<'static, str>>, error_type: impl Into<Cow<'static, str>>, msg: String, position: Position) -> Self {
+        CompileError { id: id.into(), error_type: error_type.into(), msg, position }
     }
 
     /// Prints the compile error to the screen and then exits the program
+    /// - Inside `CompileError::catch` it's returned from `catch` instead (without printing anything)
     pub fn throw<T>(&self) -> T {
+        if CATCHING.get() {
+            THROWN.with(|x| *x.borrow_mut() = Some(self.clone()));
+            panic::resume_unwind(Box::new("compile error thrown"));
+        }
         println!("{}", self);
         if cfg!(debug_assertions) {
+            THROWN.with(|x| *x.borrow_mut() = Some(self.clone()));
             panic!("error thrown in debug mode");
         }
         std::process::exit(1);
     }
+
+    /// Runs a function, returning the first compile error it throws instead of exiting (in debug and release builds)
+    /// - Other panics carry on unwinding
+    pub fn catch<T>(f: impl FnOnce() -> T) -> Result<T, CompileError> {
+        let catching = CATCHING.replace(true);
+        THROWN.with(|x| x.borrow_mut().take());
+        let result = panic::catch_unwind(AssertUnwindSafe(f));
+        CATCHING.set(catching);
+
+        result.map_err(|payload| THROWN.with(|x| x.borrow_mut().take()).unwrap_or_else(|| panic::resume_unwind(payload)))
+    }
 }
 
 impl Error for CompileError {}
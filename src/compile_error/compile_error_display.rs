@@ -6,29 +6,31 @@ pub const LINE_LIMIT: u8 = 24;
 
 impl Display for CompileError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let ln = self.position.0.ln.to_string();
-        let (line, arrw) = if self.position.0.ln == self.position.1.ln {
+        let (start, end) = (self.position.start(), self.position.end());
+        let ln = start.0.to_string();
+        let source_line = self.position.0.get_ln().unwrap_or_default();
+        let (line, arrw) = if start.0 == end.0 {
             sample(
-                self.position.0.get_ln().unwrap(), // Position should be valid
-                self.position.0.ln_idx,
-                self.position.1.ln_idx,
+                &source_line,
+                start.1,
+                end.1,
                 &self.msg,
                 false,
             )
         } else {
             sample(
-                self.position.0.get_ln().unwrap(), // Position should be valid
-                self.position.0.ln_idx,
-                self.position.0.get_ln().unwrap().len() as u16, // Position should be valid
+                &source_line,
+                start.1,
+                source_line.chars().count(),
                 &self.msg,
                 true,
             )
         };
 
         let out = colour_format![
-            red("\nerror["), yellow(self.id), red("]: "), none(self.error_type),
-            blue("\n --> "), cyan(&self.position.0.file_name),
-            blue(":"), yellow(&ln), blue(":"), yellow(&self.position.0.ln_idx.to_string()),
+            red("\nerror["), yellow(&self.id), red("]: "), none(&self.error_type),
+            blue("\n --> "), cyan(&self.position.0.file_name()),
+            blue(":"), yellow(&ln), blue(":"), yellow(&start.1.to_string()),
             none("\n"), yellow(&ln), blue(" | "), none(&line),
             none("\n"), none(&" ".repeat(ln.len())), blue(" | ") red(&arrw),
             blue("\n <--"),
@@ -38,37 +40,40 @@ impl Display for CompileError {
     }
 }
 
-fn sample(line: &str, start_idx: u16, end_idx: u16, msg: &str, multi_line: bool) -> (String, String) {
-    let start_trim = cal_trim(start_idx, 0);
-    let end_trim = cal_trim(line.len() as u16, end_idx);
+fn sample(line: &str, start_idx: usize, end_idx: usize, msg: &str, multi_line: bool) -> (String, String) {
+    let chars = line.chars().collect::<Vec<_>>();
+    let len = chars.len();
+    let end_idx = end_idx.max(start_idx); // empty positions still get a pointer
+    let start_trim = cal_trim(start_idx, 0).min(len);
+    let end_trim = cal_trim(len, end_idx).min(len - start_trim);
 
-    let mut sample = line[start_trim as usize..line.len() - end_trim as usize].to_string();
+    let mut sample = chars[start_trim..len - end_trim].iter().collect::<String>();
 
     if start_trim != 0 { sample = colour_format![cyan("..."), none(&sample)]; }
     if end_trim != 0 { sample = colour_format![none(&sample), cyan("...")]; }
 
     if multi_line { sample = colour_format![none(&sample), cyan("\\n"), red("...")]; }
 
-    (sample, gen_arrw(start_idx, end_idx, msg, start_trim, 5 * multi_line as u16))
+    (sample, gen_arrw(start_idx, end_idx, msg, start_trim, 5 * multi_line as usize))
 }
 
 #[inline]
-fn cal_trim(actual: u16, desired: u16) -> u16 {
+fn cal_trim(actual: usize, desired: usize) -> usize {
     let dif = actual.saturating_sub(desired);
-    if dif > LINE_LIMIT as u16 { dif - LINE_LIMIT as u16 + 3 } // accounts for the `...`
+    if dif > LINE_LIMIT as usize { dif - LINE_LIMIT as usize + 3 } // accounts for the `...`
     else { 0 }
 }
 
 #[inline]
-fn gen_arrw(start_idx: u16, end_idx: u16, msg: &str, start_trim: u16, offset: u16) -> String {
+fn gen_arrw(start_idx: usize, end_idx: usize, msg: &str, start_trim: usize, offset: usize) -> String {
     let spaces_since_start = if start_trim > 0 {
-        start_idx - start_trim + 2 // acounts for the `...` and padding
-    } else { start_idx -1 };
+        start_idx.saturating_sub(start_trim) + 2 // acounts for the `...` and padding
+    } else { start_idx.saturating_sub(1) };
 
-    let inbetween = end_idx - start_idx + 1 + offset; // even if it's the same character you still need a pointer
+    let inbetween = end_idx.saturating_sub(start_idx) + 1 + offset; // even if it's the same character you still need a pointer
 
-    let mut out = " ".repeat(spaces_since_start as usize);
-    out.push_str(&"^".repeat(inbetween as usize));
+    let mut out = " ".repeat(spaces_since_start);
+    out.push_str(&"^".repeat(inbetween));
     out.push(' ');
     out.push_str(&msg.replace('\n', "\x1b[36m\\n\x1b[31m"));
     
@@ -0,0 +1,258 @@
+use std::{cell::RefCell, cmp::Reverse, fmt::Display, iter::Peekable, ops::Range, sync::Arc};
+use crate::{source_map::{self, FileId}, token_node::{Token, Trivia}};
+
+/// Records the token spans of successfully parsed `parser!` nodes
+#[derive(Debug, Default)]
+pub struct Trace(RefCell<Vec<TraceSpan>>);
+
+/// The token span of a parsed `parser!` node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceSpan {
+    pub kind: &'static str,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Trace {
+    #[inline]
+    pub fn mark(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    /// Forgets every span recorded after the mark (for backtracking)
+    #[inline]
+    pub fn rewind(&self, mark: usize) {
+        self.0.borrow_mut().truncate(mark);
+    }
+
+    #[inline]
+    pub fn push(&self, span: TraceSpan) {
+        self.0.borrow_mut().push(span);
+    }
+
+    #[inline]
+    pub fn spans(&self) -> Box<[TraceSpan]> {
+        self.0.borrow().as_slice().into()
+    }
+}
+
+//////////////////////////
+// Green tree
+//////////////////////////
+
+/// An immutable, position independent node of the concrete syntax tree
+#[derive(Debug, Clone)]
+pub struct GreenNode<TT> {
+    pub kind: &'static str,
+    pub text_len: usize,
+    pub children: Box<[GreenElement<TT>]>,
+}
+
+/// An immutable, position independent token of the concrete syntax tree (with its trivia)
+#[derive(Debug, Clone)]
+pub struct GreenToken<TT> {
+    pub kind: TT,
+    pub leading: Box<str>,
+    pub text: Box<str>,
+    pub trailing: Box<str>,
+}
+
+#[derive(Debug, Clone)]
+pub enum GreenElement<TT> {
+    Node(Arc<GreenNode<TT>>),
+    Token(Arc<GreenToken<TT>>),
+    /// Trivia that isn't attached to any token (in a file without any tokens)
+    Trivia(Arc<str>),
+}
+
+impl<TT> GreenElement<TT> {
+    #[inline]
+    pub fn text_len(&self) -> usize {
+        match self {
+            Self::Node(x) => x.text_len,
+            Self::Token(x) => x.text_len(),
+            Self::Trivia(x) => x.len(),
+        }
+    }
+}
+
+impl<TT> GreenToken<TT> {
+    #[inline]
+    pub fn text_len(&self) -> usize {
+        self.leading.len() + self.text.len() + self.trailing.len()
+    }
+}
+
+impl<TT: Display + Clone> GreenNode<TT> {
+    /// Builds a lossless tree (under a `root` node) from the tokens of a file and the spans the parser traced
+    /// - A file without any tokens keeps its text (whitespace and comments) as a single trivia element of the root
+    pub fn build(file: FileId, tokens: &[Token<TT>], trace: &Trace) -> Arc<Self> {
+        let spans = trace.spans();
+        let mut order = (0..spans.len()).collect::<Vec<_>>();
+        // parents before children, and for identical spans the outer node is recorded last
+        order.sort_by_key(|i| (spans[*i].start, Reverse(spans[*i].end), Reverse(*i)));
+
+        let mut root = Self::build_node("root", 0..tokens.len(), tokens, &mut order.into_iter().map(|i| spans[i]).peekable());
+        let file = source_map::file(file);
+        let text = file.contents.source().as_str();
+        if tokens.is_empty() && !text.is_empty() {
+            root.text_len = text.len();
+            root.children = Box::new([GreenElement::Trivia(text.into())]);
+        }
+        Arc::new(root)
+    }
+
+    fn build_node(kind: &'static str, range: Range<usize>, tokens: &[Token<TT>], spans: &mut Peekable<impl Iterator<Item = TraceSpan>>) -> Self {
+        let mut children = Vec::new();
+        let mut idx = range.start;
+
+        while let Some(span) = spans.peek().copied() {
+            if span.end > range.end { break }
+            spans.next();
+            if span.start < idx { continue } // overlaps a sibling, so it can't be part of the tree
+
+            children.extend(tokens[idx..span.start].iter().map(GreenElement::from));
+            children.push(GreenElement::Node(Arc::new(Self::build_node(span.kind, span.start..span.end, tokens, spans))));
+            idx = span.end;
+        }
+        children.extend(tokens[idx..range.end].iter().map(GreenElement::from));
+
+        Self {
+            kind,
+            text_len: children.iter().map(|x| x.text_len()).sum(),
+            children: children.into_boxed_slice(),
+        }
+    }
+}
+
+impl<TT: Display + Clone> From<&Token<TT>> for GreenElement<TT> {
+    fn from(token: &Token<TT>) -> Self {
+        Self::Token(Arc::new(GreenToken {
+            kind: token.token_type.clone(),
+            leading: Trivia::text(&token.trivia.leading).into_boxed_str(),
+            text: token.position.text().into_boxed_str(),
+            trailing: Trivia::text(&token.trivia.trailing).into_boxed_str(),
+        }))
+    }
+}
+
+//////////////////////////
+// Red tree
+//////////////////////////
+
+/// A node of the concrete syntax tree that knows its parent and offset into the source
+#[derive(Debug, Clone)]
+pub struct SyntaxNode<TT> {
+    green: Arc<GreenNode<TT>>,
+    parent: Option<Arc<SyntaxNode<TT>>>,
+    offset: usize,
+}
+
+/// A token of the concrete syntax tree that knows its parent and offset into the source
+#[derive(Debug, Clone)]
+pub struct SyntaxToken<TT> {
+    green: Arc<GreenToken<TT>>,
+    parent: Arc<SyntaxNode<TT>>,
+    offset: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement<TT> {
+    Node(SyntaxNode<TT>),
+    Token(SyntaxToken<TT>),
+    /// Trivia that isn't attached to any token, and its byte range in the source
+    Trivia(Arc<str>, Range<usize>),
+}
+
+impl<TT> SyntaxNode<TT> {
+    #[inline]
+    pub fn new_root(green: Arc<GreenNode<TT>>) -> Self {
+        Self { green, parent: None, offset: 0 }
+    }
+
+    #[inline]
+    pub fn kind(&self) -> &'static str {
+        self.green.kind
+    }
+
+    #[inline]
+    pub fn green(&self) -> &Arc<GreenNode<TT>> {
+        &self.green
+    }
+
+    #[inline]
+    pub fn parent(&self) -> Option<&SyntaxNode<TT>> {
+        self.parent.as_deref()
+    }
+
+    /// The byte range of the node in the source (including trivia)
+    #[inline]
+    pub fn text_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text_len
+    }
+
+    pub fn children(&self) -> Box<[SyntaxElement<TT>]> {
+        let parent = Arc::new(Self { green: self.green.clone(), parent: self.parent.clone(), offset: self.offset });
+        let mut offset = self.offset;
+
+        self.green.children.iter()
+            .map(|x| {
+                let element = match x {
+                    GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode { green: green.clone(), parent: Some(parent.clone()), offset }),
+                    GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken { green: green.clone(), parent: parent.clone(), offset }),
+                    GreenElement::Trivia(text) => SyntaxElement::Trivia(text.clone(), offset..offset + text.len()),
+                };
+                offset += x.text_len();
+                element
+            }).collect()
+    }
+
+    /// Reconstructs the exact source text of the node
+    pub fn text(&self) -> String {
+        fn push<TT>(out: &mut String, green: &GreenNode<TT>) {
+            green.children.iter().for_each(|x| match x {
+                GreenElement::Node(x) => push(out, x),
+                GreenElement::Token(x) => { out.push_str(&x.leading); out.push_str(&x.text); out.push_str(&x.trailing) },
+                GreenElement::Trivia(x) => out.push_str(x),
+            });
+        }
+
+        let mut out = String::with_capacity(self.green.text_len);
+        push(&mut out, &self.green);
+        out
+    }
+}
+
+impl<TT> SyntaxToken<TT> {
+    #[inline]
+    pub fn kind(&self) -> &TT {
+        &self.green.kind
+    }
+
+    #[inline]
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    #[inline]
+    pub fn leading_trivia(&self) -> &str {
+        &self.green.leading
+    }
+
+    #[inline]
+    pub fn trailing_trivia(&self) -> &str {
+        &self.green.trailing
+    }
+
+    #[inline]
+    pub fn parent(&self) -> &SyntaxNode<TT> {
+        &self.parent
+    }
+
+    /// The byte range of the token in the source (excluding trivia)
+    #[inline]
+    pub fn text_range(&self) -> Range<usize> {
+        let start = self.offset + self.green.leading.len();
+        start..start + self.green.text.len()
+    }
+}
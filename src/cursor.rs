@@ -1,19 +1,22 @@
-use std::rc::Rc;
+use std::{hash::{Hash, Hasher}, sync::Arc};
+use crate::source_map::{self, FileId, SourceFile};
 
 /// A mutable cursor for the lexer
+/// - Holds onto its file so moving through it doesn't have to go through the global source map
 #[derive(Debug, Clone)]
 pub struct MutCursor {
-    pub pos_start: Rc<Cursor>,
-    pub pos_end: Rc<Cursor>,
+    pub file: Arc<SourceFile>,
+    pub pos_start: Cursor,
+    pub pos_end: Cursor,
     pub current_char: Option<char>,
 }
 
 impl MutCursor {
     /// Constructs a new mutable cursor
     pub fn new(pos_start: Cursor) -> Self {
-        let pos_start = Rc::new(pos_start);
         Self {
-            pos_start: pos_start.clone(),
+            file: pos_start.source_file(),
+            pos_start,
             pos_end: pos_start,
             current_char: None,
         }
@@ -22,14 +25,15 @@ impl MutCursor {
     /// Constructs an immutable position from the ,`MutCursor`
     #[inline]
     pub fn position(&self) -> Position {
-        Position(self.pos_start.clone(), self.pos_end.clone())
+        Position(self.pos_start, self.pos_end)
     }
 
     /// Spawns a child `MutCursor`
     pub fn spawn(&self) -> Self {
         Self {
-            pos_start: self.pos_end.clone(),
-            pos_end: self.pos_end.clone(),
+            file: self.file.clone(),
+            pos_start: self.pos_end,
+            pos_end: self.pos_end,
             current_char: self.current_char,
         }
     }
@@ -37,159 +41,304 @@ impl MutCursor {
     /// Updates the last position of the `MutCursor`
     #[inline]
     pub fn update(&mut self, cursor: Cursor) {
-        self.pos_end = Rc::new(cursor);
+        self.pos_end = cursor;
     }
 
     /// Advances through the file
+    #[inline]
     pub fn advance(&mut self) {
-        let mut cursor = self.pos_end.dupe();
-        self.current_char = cursor.advance();
-        self.update(cursor);
+        self.current_char = self.pos_end.advance_in(&self.file);
     }
 
     /// Un-advances through the file
+    #[inline]
     pub fn revance(&mut self) {
-        let mut cursor = self.pos_end.dupe();
-        self.current_char = cursor.revance();
-        self.update(cursor);
+        self.current_char = self.pos_end.revance_in(&self.file);
     }
 }
 
-/// A full position of a string of characters in a file
-#[derive(Clone, Hash, PartialEq, Eq)]
-pub struct Position(pub Rc<Cursor>, pub Rc<Cursor>);
+/// A full position of a string of characters in a file (from its start up to, but not including, its end)
+#[derive(Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Position(pub Cursor, pub Cursor);
 
 impl Position {
     /// Merges with another position to create a new position that contains both
     #[inline]
     pub fn combine(&self, other: &Position) -> Self {
-        Self(self.0.clone(), other.1.clone())
+        Self(self.0, other.1)
+    }
+
+    /// Creates a position over a single line, or over a range of its characters (both starting at one and inclusive)
+    /// - The line is added to the global source map the first time (and reused after that for the same name and line)
+    pub fn new_oneline(file_name: &str, line: &str, range: Option<(usize, usize)>) -> Self {
+        let start = Cursor::from_file(source_map::intern_file(file_name, line.to_string()));
+        let Some((from, to)) = range else { return Position(start, start.at(line.len())) };
+
+        let byte = |idx: usize| line.char_indices().nth(idx).map_or(line.len(), |(i, _)| i);
+        Position(start.at(byte(from.saturating_sub(1))), start.at(byte(to)))
+    }
+
+    /// Creates a zero-width position at a cursor
+    #[inline]
+    pub fn empty(cursor: &Cursor) -> Self {
+        Self(*cursor, *cursor)
     }
 
+    /// Creates a zero-width position at the end of a file
     #[inline]
-    pub fn new_oneline(file_name: &str, line: &str, range: Option<(u16, u16)>) -> Self {
-        let mut start = Cursor::new(file_name.to_string(), line);
-        let mut end = start.clone();
+    pub fn eof(file: FileId) -> Self {
+        let cursor = Cursor::from_file(file);
+        Self::empty(&cursor.at(cursor.source_file().contents.source().as_str().len()))
+    }
+
+    /// Checks if the position is zero-width
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.1.offset <= self.0.offset
+    }
 
-        start.ln_idx = match range {
-            Some(x) => x.0,
-            None => 1,
-        };
-        end.ln_idx = match range {
-            Some(x) => x.1,
-            None => line.len() as u16,
-        };
+    /// The line and line index (both starting at one) of the start of the position
+    #[inline]
+    pub fn start(&self) -> (usize, usize) {
+        self.0.location()
+    }
 
-        Position(Rc::new(start), Rc::new(end))
+    /// The line and line index (both starting at one) of the last character of the position
+    /// (or of right before its start if it's zero-width)
+    pub fn end(&self) -> (usize, usize) {
+        if self.is_empty() {
+            let (ln, ln_idx) = self.0.location();
+            return (ln, ln_idx - 1);
+        }
+        let mut last = self.1;
+        last.revance();
+        last.location()
+    }
+
+    /// Gets the source text that the position spans
+    pub fn text(&self) -> String {
+        if self.is_empty() { return String::new() }
+        let file = self.0.source_file();
+        let source = file.contents.source().as_str();
+        let clamp = |cursor: &Cursor| cursor.offset().min(source.len());
+        source.get(clamp(&self.0)..clamp(&self.1)).unwrap_or_default().to_string()
     }
 }
 
+/// A zero-width position at the cursor
 impl From<Cursor> for Position {
+    #[inline]
     fn from(cursor: Cursor) -> Self {
-        let cursor = Rc::new(cursor);
-        Self(cursor.clone(), cursor)
+        Self(cursor, cursor)
     }
 }
 
 impl std::fmt::Debug for Position {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f, "<position: {}:{}:{} - {}:{}:{}>",
-            self.0.file_name,
-            self.0.ln,
-            self.0.ln_idx,
-            self.1.file_name,
-            self.1.ln,
-            self.1.ln_idx+1,
-        )
+        let (start, end) = (self.0.location(), self.1.location());
+        write!(f, "<position: {}:{}:{} - {}:{}:{}>", self.0.file_name(), start.0, start.1, self.1.file_name(), end.0, end.1)
     }
 }
 
-/// Tracks the position of a character in a file
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+/// Tracks the position of a character in a file (from the global source map) as a byte offset
+/// - Lines and line indexes are only worked out (with `location`) when they're needed, like when rendering errors
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub struct Cursor {
-    pub file_name: Rc<String>,
-    file_contents: Rc<FileContents>,
-    pub ln: u16,
-    pub ln_idx: u16,
+    pub file: FileId,
+    offset: u32, // so positions fit in 16 bytes
 }
 
 impl Cursor {
+    /// Adds a file to the global source map (where it stays until it's removed) and creates a cursor at its start
+    #[inline]
     pub fn new(file_name: String, contents: &str) -> Self {
-        Self {
-            file_name: Rc::new(file_name),
-            file_contents: Rc::new(FileContents::new(contents)),
-            ln: 1,
-            ln_idx: 1,
-        }
+        Self::from_source(file_name, Source::from(contents.to_string()))
+    }
+
+    /// Adds a file to the global source map (without copying it) and creates a cursor at its start
+    #[inline]
+    pub fn from_source(file_name: String, source: Source) -> Self {
+        Self::from_file(source_map::add_file(file_name, source))
     }
 
+    /// Creates a cursor at the start of a file in the global source map
     #[inline]
-    pub fn get_ln(&self) -> Option<&'_ str> {
-        self.file_contents.0
-            .get(self.ln as usize -1) // line starts at one instead of zero
-            .map(|x| x.as_ref())
+    pub fn from_file(file: FileId) -> Self {
+        Self { file, offset: 0 }
     }
 
-    /// Clones it through a Rc
+    /// Gets the file the cursor is in (from the global source map)
+    #[inline]
+    pub fn source_file(&self) -> Arc<SourceFile> {
+        source_map::file(self.file)
+    }
+
+    #[inline]
+    pub fn file_name(&self) -> Arc<str> {
+        self.source_file().name.clone()
+    }
+
+    /// The byte offset of the cursor in its file
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset as usize
+    }
+
+    /// The line and line index of the cursor (both starting at one)
+    #[inline]
+    pub fn location(&self) -> (usize, usize) {
+        self.source_file().contents.location(self.offset())
+    }
+
+    #[inline]
+    pub fn ln(&self) -> usize {
+        self.location().0
+    }
+
+    #[inline]
+    pub fn ln_idx(&self) -> usize {
+        self.location().1
+    }
+
+    /// Gets the line the cursor is on (without its newline)
+    #[inline]
+    pub fn get_ln(&self) -> Option<String> {
+        let file = self.source_file();
+        file.contents.line(self.location_in(&file).0 - 1).map(String::from)
+    }
+
+    /// Copies the cursor
     #[inline]
     pub fn dupe(&self) -> Self {
-        self.clone()
+        *self
+    }
+
+    /// Creates a cursor at a byte offset of the same file
+    #[inline]
+    pub fn at(&self, offset: usize) -> Self {
+        Self { offset: offset.try_into().expect("files can't be bigger than 4GiB"), ..*self }
+    }
+
+    /// Creates a cursor at a line and line index (both starting at one) of the same file
+    #[inline]
+    pub fn moved(&self, ln: usize, ln_idx: usize) -> Self {
+        self.at(self.source_file().contents.offset(ln, ln_idx))
     }
 
     #[inline]
     pub fn get_char(&self) -> Option<char> {
-        self.get_ln().unwrap()
-            .chars()
-            .collect::<Box<[char]>>()
-            .get(self.ln_idx as usize -1)
-            .copied()
+        self.get_char_in(&self.source_file())
+    }
+
+    /// Checks if the cursor is on the imaginary newline at the end of the last line
+    #[inline]
+    pub fn is_eof(&self) -> bool {
+        self.offset() >= self.source_file().contents.source().as_str().len()
     }
 
+    #[inline]
     pub fn advance(&mut self) -> Option<char> {
-        let line_len = self.get_ln().unwrap().len();
+        self.advance_in(&self.source_file())
+    }
 
-        if line_len == 0 && self.ln_idx == 1 {
-            self.ln_idx += 1; return Some('\n');
-        }
+    #[inline]
+    pub fn revance(&mut self) -> Option<char> {
+        self.revance_in(&self.source_file())
+    }
 
-        if self.ln_idx as usize == line_len { // still have to return end of line
-            self.ln_idx += 1; return Some('\n');
-        }
+    /// The line and line index of the cursor in its file (that's already been looked up)
+    #[inline]
+    pub fn location_in(&self, file: &SourceFile) -> (usize, usize) {
+        file.contents.location(self.offset())
+    }
 
-        if self.ln_idx as usize > line_len { // if reached end of line
-            if self.ln as usize == self.file_contents.0.len() { return None; } // if reached last line
-            self.ln += 1;
-            self.ln_idx = 0;
-            return self.advance();
-        } else {
-            self.ln_idx += 1;
+    /// Gets the current character from the cursor's file (that's already been looked up)
+    #[inline]
+    pub fn get_char_in(&self, file: &SourceFile) -> Option<char> {
+        let source = file.contents.source().as_str();
+        match source.get(self.offset()..)?.chars().next() {
+            None => Some('\n'), // the imaginary newline at the end of the last line
+            x => x,
         }
-
-        self.get_char()
     }
 
-    pub fn revance(&mut self) -> Option<char> {
-        if self.ln_idx == 1 { // if reached start of line
-            if self.ln == 1 { return None; } // if reached first line
-            self.ln -= 1;
-            self.ln_idx = self.get_ln().unwrap().len() as u16;
-        } else {
-            self.ln_idx -= 1;
-        }
+    /// Advances through the cursor's file (that's already been looked up)
+    pub fn advance_in(&mut self, file: &SourceFile) -> Option<char> {
+        let current = file.contents.source().as_str().get(self.offset()..)?.chars().next()?;
+        self.offset += current.len_utf8() as u32;
+        self.get_char_in(file)
+    }
 
-        self.get_char()
+    /// Un-advances through the cursor's file (that's already been looked up)
+    pub fn revance_in(&mut self, file: &SourceFile) -> Option<char> {
+        let previous = file.contents.source().as_str().get(..self.offset())?.chars().next_back()?;
+        self.offset -= previous.len_utf8() as u32;
+        Some(previous)
     }
 }
 
-/// Holds the contents of a file
+/// Holds the contents of a file (split into lines)
 #[derive(Hash, PartialEq, Eq)]
-pub struct FileContents(pub Box<[Box<str>]>);
+pub struct FileContents {
+    source: Source,
+    lines: Box<[usize]>, // the byte offset of the start of each line
+}
 
 impl FileContents {
     #[inline]
     pub fn new(contents: &str) -> Self {
-        Self(contents.split('\n').map(|x| x.into()).collect())
+        Self::from_source(Source::from(contents.to_string()))
+    }
+
+    pub fn from_source(source: Source) -> Self {
+        assert!(u32::try_from(source.as_str().len()).is_ok(), "files can't be bigger than 4GiB");
+        let lines = std::iter::once(0)
+            .chain(source.as_str().match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { source, lines }
+    }
+
+    /// Gets a line (starting at zero) without its newline
+    #[inline]
+    pub fn line(&self, idx: usize) -> Option<&str> {
+        let start = *self.lines.get(idx)?;
+        let end = self.lines.get(idx + 1).map_or(self.source.as_str().len(), |x| x - 1);
+        Some(&self.source.as_str()[start..end])
+    }
+
+    /// Converts a line and line index (both starting at one) into a byte offset
+    /// (past the end of a line is its newline, and past the last line is the end of the file)
+    #[inline]
+    pub fn offset(&self, ln: usize, ln_idx: usize) -> usize {
+        let Some(line) = ln.checked_sub(1).and_then(|x| self.line(x)) else { return self.source.as_str().len() };
+        let idx = ln_idx.saturating_sub(1);
+        self.lines[ln - 1] + line.char_indices().nth(idx).map_or(line.len(), |(i, _)| i)
+    }
+
+    /// Converts a byte offset into a line and line index (both starting at one)
+    #[inline]
+    pub fn location(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.as_str().len());
+        let ln = self.lines.partition_point(|x| *x <= offset);
+        let start = self.lines[ln - 1];
+        (ln, self.source.as_str().get(start..offset).map_or(offset - start, |x| x.chars().count()) + 1)
+    }
+
+    /// The amount of lines in the file
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Always false, as even an empty file has a line
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    pub fn source(&self) -> &Source {
+        &self.source
     }
 }
 
@@ -198,3 +347,66 @@ impl std::fmt::Debug for FileContents {
         write!(f, "<file contents>")
     }
 }
+
+/// The text of a file, which is shared instead of copied where possible
+#[derive(Debug, Clone)]
+pub enum Source {
+    Static(&'static str),
+    Shared(Arc<str>),
+    Owned(Box<str>),
+}
+
+impl Source {
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Static(x) => x,
+            Self::Shared(x) => x,
+            Self::Owned(x) => x,
+        }
+    }
+}
+
+impl From<&'static str> for Source {
+    #[inline]
+    fn from(text: &'static str) -> Self {
+        Self::Static(text)
+    }
+}
+
+impl From<Arc<str>> for Source {
+    #[inline]
+    fn from(text: Arc<str>) -> Self {
+        Self::Shared(text)
+    }
+}
+
+impl From<String> for Source {
+    #[inline]
+    fn from(text: String) -> Self {
+        Self::Owned(text.into_boxed_str())
+    }
+}
+
+impl From<Box<str>> for Source {
+    #[inline]
+    fn from(text: Box<str>) -> Self {
+        Self::Owned(text)
+    }
+}
+
+impl Hash for Source {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl PartialEq for Source {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Source {}
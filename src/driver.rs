@@ -0,0 +1,150 @@
+//! Lexes and parses many files in parallel
+//! ```ignore
+//! let output = Driver::new(Token::tokenize_with, Stmt::parse).run(["main.fx", "lib.fx"]);
+//! for error in output.diagnostics() { println!("{error}") }
+//! ```
+use std::{fmt::{Debug, Display}, fs, io, num::NonZeroUsize, path::{Path, PathBuf}, sync::{atomic::{AtomicUsize, Ordering}, Mutex}, thread};
+use crate::{compile_error::CompileError, cursor::Position, incremental::{Item, ItemParser, Tokenizer}, lext::Lext, parxt::{parse_item, Parxt}, source_map::{self, FileId}, token_node::Token};
+
+/// Lexes and parses files in parallel with a `lexer!` tokenizer and a `parser!` function for top-level items
+pub struct Driver<TT: Display, N: Debug> {
+    tokenize: Tokenizer<TT>,
+    parse: ItemParser<TT, N>,
+    threads: Option<NonZeroUsize>,
+}
+
+/// A lexed and parsed file
+/// - Files that the driver read itself are removed from the global source map when their output is dropped
+#[derive(Debug)]
+pub struct Parsed<TT: Display, N: Debug> {
+    pub file: FileId,
+    pub tokens: Box<[Token<TT>]>,
+    pub items: Vec<Item<N>>,
+    /// The error the lexer threw (which leaves the file without any tokens or items)
+    pub lex_error: Option<CompileError>,
+    owned: bool,
+}
+
+/// The output of a driver for a single path
+#[derive(Debug)]
+pub struct FileOutput<TT: Display, N: Debug> {
+    pub path: PathBuf,
+    pub result: io::Result<Parsed<TT, N>>,
+}
+
+/// The output of a driver, in the same order as its input
+#[derive(Debug)]
+pub struct DriverOutput<TT: Display, N: Debug> {
+    pub files: Vec<FileOutput<TT, N>>,
+}
+
+impl<TT: Display + Send, N: Debug + Send> Driver<TT, N> {
+    #[inline]
+    pub fn new(tokenize: Tokenizer<TT>, parse: ItemParser<TT, N>) -> Self {
+        Self { tokenize, parse, threads: None }
+    }
+
+    /// Sets the maximum amount of threads to use (defaults to the available parallelism)
+    #[inline]
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = NonZeroUsize::new(threads);
+        self
+    }
+
+    /// Reads, lexes and parses every file in parallel
+    pub fn run<P: AsRef<Path>>(&self, paths: impl IntoIterator<Item = P>) -> DriverOutput<TT, N> {
+        let paths = paths.into_iter().map(|x| x.as_ref().to_path_buf()).collect::<Vec<_>>();
+        let results = self.map(&paths, |path| {
+            let contents = fs::read_to_string(path)?;
+            let mut parsed = self.parse_file(source_map::add_file(path.display().to_string(), contents));
+            parsed.owned = true;
+            Ok(parsed)
+        });
+
+        DriverOutput {
+            files: paths.into_iter().zip(results).map(|(path, result)| FileOutput { path, result }).collect(),
+        }
+    }
+
+    /// Lexes and parses files that are already in the global source map in parallel
+    #[inline]
+    pub fn run_files(&self, files: &[FileId]) -> Vec<Parsed<TT, N>> {
+        self.map(files, |x| self.parse_file(*x))
+    }
+
+    /// Lexes and parses a single file, skipping a token to recover from every error
+    /// (and catching the error the lexer throws instead of exiting)
+    pub fn parse_file(&self, file: FileId) -> Parsed<TT, N> {
+        let tokens = match CompileError::catch(|| (self.tokenize)(Lext::from_file(file), &mut |_| false)) {
+            Ok(tokens) => tokens,
+            Err(error) => return Parsed { file, tokens: Box::new([]), items: Vec::new(), lex_error: Some(error), owned: false },
+        };
+        let mut parxt = Parxt::new(&tokens).with_eof(Position::eof(file));
+        let mut items = Vec::new();
+
+        while parxt.current().is_some() {
+            let (tokens, node) = parse_item(&mut parxt, self.parse, |_, x| Ok(x));
+            items.push(Item { tokens, node });
+        }
+
+        Parsed { file, tokens, items, lex_error: None, owned: false }
+    }
+
+    /// Maps every input on a pool of scoped threads, keeping the order of the inputs
+    fn map<I: Sync, O: Send>(&self, inputs: &[I], f: impl Fn(&I) -> O + Sync) -> Vec<O> {
+        let threads = self.threads
+            .or_else(|| thread::available_parallelism().ok())
+            .map_or(1, NonZeroUsize::get)
+            .min(inputs.len());
+        let next = AtomicUsize::new(0);
+        let outputs = inputs.iter().map(|_| Mutex::new(None)).collect::<Vec<_>>();
+
+        thread::scope(|scope| for _ in 0..threads {
+            scope.spawn(|| loop {
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                let Some(input) = inputs.get(idx) else { break };
+                *outputs[idx].lock().unwrap() = Some(f(input));
+            });
+        });
+
+        outputs.into_iter()
+            .map(|x| x.into_inner().unwrap().expect("every input is mapped"))
+            .collect()
+    }
+}
+
+impl<TT: Display, N: Debug> Parsed<TT, N> {
+    /// Every error of the lexer and the top-level items
+    #[inline]
+    pub fn errors(&self) -> impl Iterator<Item = &CompileError> {
+        self.lex_error.iter().chain(self.items.iter().filter_map(|x| x.node.as_ref().err()))
+    }
+}
+
+impl<TT: Display, N: Debug> Drop for Parsed<TT, N> {
+    /// Removes the file from the global source map if the driver read it
+    #[inline]
+    fn drop(&mut self) {
+        if self.owned { source_map::remove_file(self.file); }
+    }
+}
+
+impl<TT: Display, N: Debug> DriverOutput<TT, N> {
+    /// Every compile error, ordered by the input order of their files and then by their positions
+    pub fn diagnostics(&self) -> Vec<&CompileError> {
+        self.files.iter()
+            .filter_map(|x| x.result.as_ref().ok())
+            .flat_map(|x| {
+                let mut errors = x.errors().collect::<Vec<_>>();
+                errors.sort_by_key(|x| x.position.0.offset());
+                errors
+            })
+            .collect()
+    }
+
+    /// Every file that couldn't be read
+    #[inline]
+    pub fn io_errors(&self) -> impl Iterator<Item = (&Path, &io::Error)> {
+        self.files.iter().filter_map(|x| x.result.as_ref().err().map(|e| (x.path.as_path(), e)))
+    }
+}
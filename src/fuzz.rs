@@ -0,0 +1,433 @@
+//! Fuzzing and property tests for lexers and parsers (requires the `fuzz` feature, but no dependencies)
+//! - `Fuzzer` feeds random strings through a `lexer!` tokenizer (and a `parser!` function), checking that nothing panics
+//!   and that every position is well-formed, and shrinks any input that fails
+//! - `Grammar` generates valid programs (from the rules of `parser!` functions, along with the text of every token) to check
+//!   that they lex and parse without errors
+//! - Compile errors thrown by the lexer are caught (see `CompileError::catch`) and checked like any other error
+//! ```ignore
+//! let mut fuzzer = Fuzzer::new(Token::tokenize_with).parser(Stmt::parse).fragments(["let", "=", ";"]);
+//! fuzzer.run(1000);
+//!
+//! let grammar = Grammar::new()
+//!     .parser(Stmt::parse) // adds `Stmt::parse` and every rule it uses
+//!     .token("Int", Gen::pick(["1", "2"]))
+//!     .token("Semi", Gen::text(";"))
+//!     .rule("program", Gen::repeat(Gen::rule("Stmt::parse"), 1, 4));
+//! fuzzer.round_trip(&grammar, "program", 100);
+//! ```
+use std::{cell::{Cell, RefCell}, collections::HashMap, fmt::{self, Debug, Display}, panic::{self, AssertUnwindSafe}};
+use crate::{compile_error::CompileError, cursor::Position, incremental::{ItemParser, Tokenizer}, lext::Lext, parxt::{parse_item, ParseError, Parxt}, source_map::{self, FileId}, token_node::{Node, Token}};
+
+/// Parses a token stream, returning the compile errors of its items
+type Parse<TT> = Box<dyn Fn(&[Token<TT>], Position) -> Vec<CompileError>>;
+
+/// An input that made a lexer or parser misbehave
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub input: String,
+    pub message: String,
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (input: {:?})", self.message, self.input)
+    }
+}
+
+/// Feeds inputs through a lexer (and parser) and checks that they behave
+/// - Every input is swapped into the same file of the global source map, which is removed once the fuzzer is dropped
+pub struct Fuzzer<TT: Display> {
+    tokenize: Tokenizer<TT>,
+    parse: Option<Parse<TT>>,
+    rng: Rng,
+    fragments: Vec<String>,
+    max_len: usize,
+    file: FileId,
+}
+
+impl<TT: Display + 'static> Fuzzer<TT> {
+    #[inline]
+    pub fn new(tokenize: Tokenizer<TT>) -> Self {
+        let file = source_map::add_file("<fuzz>", String::new());
+        Self { tokenize, parse: None, rng: Rng::new(0x5eed), fragments: Vec::new(), max_len: 64, file }
+    }
+
+    /// Also parses the tokens into top-level items (skipping a token to recover from every error)
+    pub fn parser<N: Debug + 'static>(mut self, parse: ItemParser<TT, N>) -> Self {
+        self.parse = Some(Box::new(move |tokens, eof| {
+            let mut parxt = Parxt::new(tokens).with_eof(eof);
+            let mut errors = Vec::new();
+            while parxt.current().is_some() {
+                if let Err(error) = parse_item(&mut parxt, parse, |_, x| Ok(x)).1 { errors.push(error) }
+            } errors
+        }));
+        self
+    }
+
+    /// Sets the seed of the random inputs
+    #[inline]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    /// Adds pieces of text (like keywords and operators) that random inputs are built from, along with random characters
+    #[inline]
+    pub fn fragments<S: ToString>(mut self, fragments: impl IntoIterator<Item = S>) -> Self {
+        self.fragments.extend(fragments.into_iter().map(|x| x.to_string()));
+        self
+    }
+
+    /// Sets the maximum amount of pieces in a random input
+    #[inline]
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Checks a single input
+    pub fn check(&self, input: &str) -> Result<(), Failure> {
+        let fail = |message: String| Failure { input: input.to_string(), message };
+        let (file, tokenize) = (self.file, self.tokenize);
+        source_map::update_file(file, input.to_string());
+
+        let tokens = match catch(|| tokenize(Lext::from_file(file), &mut |_| false)) {
+            Ok(tokens) => tokens,
+            Err(Thrown::Error(error)) => return check_error(&error).map_err(fail),
+            Err(Thrown::Panic(message)) => return Err(fail(format!("lexer panicked: {message}"))),
+        };
+
+        let mut last = None;
+        for token in tokens.iter() {
+            check_position(&token.position).map_err(|x| fail(format!("token `{}` {x}", token.token_type)))?;
+            for piece in token.trivia.leading.iter().chain(token.trivia.trailing.iter()) {
+                check_position(&piece.position).map_err(|x| fail(format!("trivia of `{}` {x}", token.token_type)))?;
+            }
+
+            let start = token.position.0.offset();
+            if last.is_some_and(|x| x > start) { return Err(fail(format!("token `{}` starts before the token before it", token.token_type))) }
+            last = Some(start);
+        }
+
+        let Some(parse) = &self.parse else { return Ok(()) };
+        match catch(|| parse(&tokens, Position::eof(file))) {
+            Ok(errors) => errors.iter().try_for_each(check_error).map_err(fail),
+            Err(Thrown::Error(error)) => check_error(&error).map_err(fail),
+            Err(Thrown::Panic(message)) => Err(fail(format!("parser panicked: {message}"))),
+        }
+    }
+
+    /// Checks random inputs, panicking with the smallest version of the first one that fails
+    pub fn run(&mut self, iterations: usize) {
+        for _ in 0..iterations {
+            let input = self.input();
+            if let Err(failure) = self.check(&input) { panic!("{}", self.shrink(failure)) }
+        }
+    }
+
+    /// Checks that programs generated from a grammar lex and parse without any errors
+    pub fn round_trip(&mut self, grammar: &Grammar, start: &str, iterations: usize) {
+        for _ in 0..iterations {
+            let input = grammar.generate(start, &mut self.rng);
+            if let Err(failure) = self.check(&input) { panic!("{failure}") }
+
+            let (file, tokenize) = (self.file, self.tokenize); // still holds the input from `check`
+            let errors = match catch(|| tokenize(Lext::from_file(file), &mut |_| false)) {
+                Ok(tokens) => self.parse.as_ref().map_or(Vec::new(), |x| x(&tokens, Position::eof(file))),
+                Err(Thrown::Error(error)) => vec![error],
+                Err(Thrown::Panic(message)) => panic!("lexer panicked: {message} (input: {input:?})"),
+            };
+            if let Some(error) = errors.first() { panic!("generated program has errors (input: {input:?}):{error}") }
+        }
+    }
+
+    /// Generates a random input
+    fn input(&mut self) -> String {
+        const CHARS: &[char] = &[' ', '\n', '\t', '\r', '"', '\'', '\\', '/', '*', '(', ')', '{', '}', '_', '0', '9', 'a', 'Z', '.', 'é', '💥', '\0'];
+        let len = self.rng.below(self.max_len + 1);
+        (0..len).map(|_| match self.rng.below(3) {
+            0 if !self.fragments.is_empty() => self.fragments[self.rng.below(self.fragments.len())].clone(),
+            1 => char::from_u32(self.rng.below(0x80) as u32).unwrap_or(' ').to_string(),
+            _ => CHARS[self.rng.below(CHARS.len())].to_string(),
+        }).collect()
+    }
+
+    /// Removes as much of a failing input as possible while it still fails
+    fn shrink(&self, mut failure: Failure) -> Failure {
+        let mut chunk = failure.input.chars().count() / 2;
+        while chunk > 0 {
+            let mut start = 0;
+            while start < failure.input.chars().count() {
+                let smaller = failure.input.chars().take(start).chain(failure.input.chars().skip(start + chunk)).collect::<String>();
+                match self.check(&smaller) {
+                    Err(x) => failure = x,
+                    Ok(()) => start += chunk,
+                }
+            }
+            chunk /= 2;
+        } failure
+    }
+}
+
+impl<TT: Display> Drop for Fuzzer<TT> {
+    /// Removes the fuzzer's file from the global source map
+    #[inline]
+    fn drop(&mut self) {
+        source_map::remove_file(self.file);
+    }
+}
+
+/// Checks that a position is inside its file, on character boundaries and doesn't end before it starts
+pub fn check_position(position: &Position) -> Result<(), String> {
+    let file = position.0.source_file();
+    let source = file.contents.source().as_str();
+
+    if position.0.file != position.1.file { return Err(format!("has a position across files: {position:?}")) }
+    if !source.is_char_boundary(position.0.offset()) { return Err(format!("starts outside of its file: {position:?}")) }
+    if !source.is_char_boundary(position.1.offset()) { return Err(format!("ends outside of its file: {position:?}")) }
+    if position.1.offset() < position.0.offset() { return Err(format!("ends before it starts: {position:?}")) }
+    Ok(())
+}
+
+/// Checks the position of a compile error and that it can be displayed
+fn check_error(error: &CompileError) -> Result<(), String> {
+    check_position(&error.position).map_err(|x| format!("error `{}` {x}", error.id))?;
+    match catch(|| error.to_string()) {
+        Ok(_) => Ok(()),
+        Err(Thrown::Panic(message) | Thrown::Error(CompileError { msg: message, .. })) => Err(format!("error `{}` panicked while displaying: {message}", error.id)),
+    }
+}
+
+/// What a panic was caused by
+enum Thrown {
+    Error(CompileError),
+    Panic(String),
+}
+
+/// Runs a function, catching panics and thrown compile errors
+fn catch<T>(f: impl FnOnce() -> T) -> Result<T, Thrown> {
+    match panic::catch_unwind(AssertUnwindSafe(|| CompileError::catch(f))) {
+        Ok(Ok(x)) => Ok(x),
+        Ok(Err(error)) => Err(Thrown::Error(error)),
+        Err(payload) => Err(Thrown::Panic(payload.downcast_ref::<&str>().map(|x| x.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "<unknown panic>".into()))),
+    }
+}
+
+/// A tiny random number generator (xorshift64*) so fuzzing is reproducible with a seed
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    #[inline]
+    pub fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    #[inline]
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A random number below `n` (which has to be above zero)
+    #[inline]
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "there's no number below zero");
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// A piece of a grammar that generates source text
+#[derive(Debug, Clone)]
+pub enum Gen {
+    /// Exact text
+    Text(String),
+    /// One of some pieces of text (there has to be at least one)
+    Pick(Vec<String>),
+    /// Every generator, one after the other
+    Seq(Vec<Gen>),
+    /// One of the generators (there has to be at least one, and once the grammar is too deep the one that uses the fewest
+    /// rules is used)
+    Choice(Vec<Gen>),
+    /// A generator repeated between `min` and `max` times (or `min` times if `max` is below it)
+    Repeat(Box<Gen>, usize, usize),
+    /// A rule of the grammar
+    Rule(String),
+    /// The text of a token type of the grammar (the token types of `parser!` rules)
+    Token(String),
+}
+
+impl Gen {
+    #[inline]
+    pub fn text(text: impl ToString) -> Self {
+        Self::Text(text.to_string())
+    }
+
+    /// Panics without any pieces of text
+    #[inline]
+    pub fn pick<S: ToString>(texts: impl IntoIterator<Item = S>) -> Self {
+        let texts = texts.into_iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        assert!(!texts.is_empty(), "there's nothing to pick from");
+        Self::Pick(texts)
+    }
+
+    #[inline]
+    pub fn seq(gens: impl IntoIterator<Item = Gen>) -> Self {
+        Self::Seq(gens.into_iter().collect())
+    }
+
+    /// Panics without any generators
+    #[inline]
+    pub fn choice(gens: impl IntoIterator<Item = Gen>) -> Self {
+        let gens = gens.into_iter().collect::<Vec<_>>();
+        assert!(!gens.is_empty(), "there's nothing to choose from");
+        Self::Choice(gens)
+    }
+
+    /// Panics if `max` is below `min`
+    #[inline]
+    pub fn repeat(gen: Gen, min: usize, max: usize) -> Self {
+        assert!(min <= max, "can't repeat at least {min} and at most {max} times");
+        Self::Repeat(Box::new(gen), min, max)
+    }
+
+    #[inline]
+    pub fn rule(name: impl ToString) -> Self {
+        Self::Rule(name.to_string())
+    }
+
+    #[inline]
+    pub fn token(name: impl ToString) -> Self {
+        Self::Token(name.to_string())
+    }
+
+    /// How many rules a generator uses at the least (without following them)
+    fn rules(&self) -> usize {
+        match self {
+            Self::Text(_) | Self::Pick(_) | Self::Token(_) => 0,
+            Self::Seq(x) => x.iter().map(Self::rules).sum(),
+            Self::Choice(x) => x.iter().map(Self::rules).min().unwrap_or(0),
+            Self::Repeat(x, min, _) => x.rules() * min,
+            Self::Rule(_) => 1,
+        }
+    }
+}
+
+/// Records the grammar of `parser!` functions (used by the `parser!` macro)
+#[doc(hidden)]
+#[derive(Debug, Default)]
+pub struct Describe {
+    rules: RefCell<HashMap<&'static str, Option<Gen>>>,
+    last: Cell<Option<&'static str>>,
+}
+
+impl Describe {
+    /// Describes the `parser!` function that a function calls, as a rule (or a rule named after the function's code if it
+    /// doesn't call one)
+    pub fn call<TT: Display>(&self, name: &'static str, f: impl FnOnce(&mut Parxt<'_, TT>)) -> Gen {
+        self.last.set(None);
+        f(&mut Parxt::new(&[]).describing(self));
+        Gen::rule(self.last.take().unwrap_or(name))
+    }
+
+    /// Records the rule of a `parser!` function (unless it's already recorded or being recorded)
+    pub fn rule(&self, name: &'static str, position: Position, gen: impl FnOnce() -> Gen) -> ParseError {
+        if !self.rules.borrow().contains_key(name) {
+            self.rules.borrow_mut().insert(name, None); // being recorded (for recursive rules)
+            let gen = gen();
+            self.rules.borrow_mut().insert(name, Some(gen));
+        }
+        self.last.set(Some(name));
+        ParseError::new(0, CompileError::new("", "", String::new(), position))
+    }
+}
+
+/// Rules that generate valid programs (with pieces separated by spaces)
+/// - The rules of `parser!` functions are named after their node and function (like `Expr::parse`), and sub-rules that
+///   aren't `parser!` functions are named after their code (so they can be written by hand)
+#[derive(Debug, Clone)]
+pub struct Grammar {
+    rules: HashMap<String, Gen>,
+    tokens: HashMap<String, Gen>,
+    max_depth: usize,
+}
+
+impl Default for Grammar {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Grammar {
+    #[inline]
+    pub fn new() -> Self {
+        Self { rules: HashMap::new(), tokens: HashMap::new(), max_depth: 8 }
+    }
+
+    #[inline]
+    pub fn rule(mut self, name: impl ToString, gen: Gen) -> Self {
+        self.rules.insert(name.to_string(), gen);
+        self
+    }
+
+    /// Sets the text of a token type (by the name of its variant)
+    #[inline]
+    pub fn token(mut self, name: impl ToString, gen: Gen) -> Self {
+        self.tokens.insert(name.to_string(), gen);
+        self
+    }
+
+    /// Adds the rules of a `parser!` function and every `parser!` function it uses
+    pub fn parser<TT: Display, N: Debug>(mut self, parse: impl FnOnce(&mut Parxt<'_, TT>) -> Result<Node<N>, ParseError>) -> Self {
+        let describe = Describe::default();
+        describe.call("", |x| { let _ = parse(x); });
+        self.rules.extend(describe.rules.into_inner().into_iter().map(|(name, gen)| (name.to_string(), gen.expect("every rule is recorded"))));
+        self
+    }
+
+    /// Sets how deep rules can nest before choices only use their simplest option
+    #[inline]
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Generates a random program from a rule
+    pub fn generate(&self, start: &str, rng: &mut Rng) -> String {
+        let mut pieces = Vec::new();
+        self.gen(&Gen::rule(start), rng, 0, &mut pieces);
+        pieces.join(" ")
+    }
+
+    fn gen(&self, gen: &Gen, rng: &mut Rng, depth: usize, pieces: &mut Vec<String>) {
+        match gen {
+            Gen::Text(x) => pieces.push(x.clone()),
+            Gen::Pick(x) if x.is_empty() => panic!("grammar has a pick without any text"),
+            Gen::Pick(x) => pieces.push(x[rng.below(x.len())].clone()),
+            Gen::Seq(x) => x.iter().for_each(|x| self.gen(x, rng, depth, pieces)),
+            Gen::Choice(x) if x.is_empty() => panic!("grammar has a choice without any options"),
+            Gen::Choice(x) if depth >= self.max_depth => {
+                let simplest = x.iter().min_by_key(|x| x.rules()).expect("it isn't empty");
+                self.gen(simplest, rng, depth, pieces)
+            },
+            Gen::Choice(x) => self.gen(&x[rng.below(x.len())], rng, depth, pieces),
+            Gen::Repeat(x, min, max) => {
+                let count = if depth >= self.max_depth { *min } else { min + rng.below(max.saturating_sub(*min) + 1) };
+                (0..count).for_each(|_| self.gen(x, rng, depth, pieces));
+            },
+            Gen::Rule(name) => {
+                let rule = self.rules.get(name).unwrap_or_else(|| panic!("grammar doesn't have a `{name}` rule"));
+                self.gen(rule, rng, depth + 1, pieces);
+            },
+            Gen::Token(name) => {
+                let token = self.tokens.get(name).unwrap_or_else(|| panic!("grammar doesn't have the text of the `{name}` token"));
+                self.gen(token, rng, depth, pieces);
+            },
+        }
+    }
+}
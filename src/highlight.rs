@@ -0,0 +1,113 @@
+use std::fmt::Display;
+use crate::token_node::Token;
+
+/// The class of a token, used for syntax highlighting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Keyword,
+    Operator,
+    Number,
+    String,
+    Comment,
+    Identifier,
+    Punctuation,
+    Other,
+}
+
+/// Classifies tokens (generated by the `lexer!` macro from the classes in `token_types`)
+pub trait Classify {
+    fn kind(&self) -> TokenKind;
+}
+
+/// The output format of the highlighter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Terminal colours
+    Ansi,
+    /// `<span class="...">` elements (for example `<span class="keyword">let</span>`)
+    Html,
+}
+
+impl TokenKind {
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Keyword => "keyword",
+            Self::Operator => "operator",
+            Self::Number => "number",
+            Self::String => "string",
+            Self::Comment => "comment",
+            Self::Identifier => "identifier",
+            Self::Punctuation => "punctuation",
+            Self::Other => "other",
+        }
+    }
+
+    #[inline]
+    pub fn ansi(&self) -> &'static str {
+        match self {
+            Self::Keyword => "\x1b[35m",
+            Self::Operator => "\x1b[36m",
+            Self::Number => "\x1b[33m",
+            Self::String => "\x1b[32m",
+            Self::Comment => "\x1b[90m",
+            Self::Identifier => "\x1b[34m",
+            Self::Punctuation | Self::Other => "\x1b[0m",
+        }
+    }
+}
+
+/// Renders a source file with its tokens highlighted
+/// - Text in-between tokens that isn't whitespace (comments that were skipped or are trivia) is highlighted as a comment
+pub fn highlight<TT: Display + Classify>(contents: &str, tokens: &[Token<TT>], format: Format) -> String {
+    let offset = |x: usize| x.min(contents.len());
+    let mut out = String::with_capacity(contents.len());
+    let mut last = 0;
+    for token in tokens {
+        let start = offset(token.position.0.offset()).max(last);
+        let end = offset(token.position.1.offset()).max(start);
+        push_gap(&mut out, &contents[last..start], format);
+        push(&mut out, &contents[start..end], token.token_type.kind(), format);
+        last = end;
+    }
+    push_gap(&mut out, &contents[last..], format);
+    out
+}
+
+/// Pushes the text between tokens, highlighting anything that isn't whitespace as a comment
+fn push_gap(out: &mut String, gap: &str, format: Format) {
+    let mut rest = gap;
+    while !rest.is_empty() {
+        let space = rest.find(|x: char| !x.is_whitespace()).unwrap_or(rest.len());
+        out.push_str(&rest[..space]);
+        rest = &rest[space..];
+
+        let text = rest.find(['\n', '\r']).unwrap_or(rest.len());
+        push(out, &rest[..text], TokenKind::Comment, format);
+        rest = &rest[text..];
+    }
+}
+
+fn push(out: &mut String, text: &str, kind: TokenKind, format: Format) {
+    if text.is_empty() { return }
+    match format {
+        Format::Ansi => {
+            out.push_str(kind.ansi());
+            out.push_str(text);
+            out.push_str("\x1b[0m");
+        },
+        Format::Html => {
+            out.push_str("<span class=\"");
+            out.push_str(kind.name());
+            out.push_str("\">");
+            text.chars().for_each(|x| match x {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '"' => out.push_str("&quot;"),
+                x => out.push(x),
+            });
+            out.push_str("</span>");
+        },
+    }
+}
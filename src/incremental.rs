@@ -0,0 +1,309 @@
+use std::{fmt::{Debug, Display}, ops::Range};
+use crate::{compile_error::CompileError, cursor::Position, lext::{LexState, Lext}, parxt::{parse_item, ParseError, Parxt}, source_map::{self, FileId}, token_node::{Node, Token, Trivia, TriviaPiece}, visit::{Fold, Walk}};
+
+/// The generated `tokenize_with` function of a `lexer!` token type
+pub type Tokenizer<TT> = fn(Lext, &mut dyn FnMut(&Token<TT>) -> bool) -> Box<[Token<TT>]>;
+/// The generated `tokenize_from` function of a `lexer!` token type
+pub type ResumableTokenizer<TT> = fn(Lext, &mut dyn FnMut(&Token<TT>, &LexState) -> bool) -> Box<[Token<TT>]>;
+/// A `parser!` function that parses a single top-level item
+pub type ItemParser<TT, N> = fn(&mut Parxt<'_, TT>) -> Result<Node<N>, ParseError>;
+
+/// An edit to a file, in the `(ln, ln_idx)` coordinates of the file *before* the edit (end exclusive)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub text: String,
+}
+
+impl TextEdit {
+    #[inline]
+    pub fn new(start: (usize, usize), end: (usize, usize), text: impl Into<String>) -> Self {
+        Self { start, end, text: text.into() }
+    }
+
+    /// The byte range that the edit replaces in the contents of a file
+    pub fn range(&self, contents: &str) -> Result<Range<usize>, EditError> {
+        let range = Self::offset(contents, self.start)?..Self::offset(contents, self.end)?;
+        if range.start > range.end { return Err(EditError::Inverted(self.start, self.end)) }
+        Ok(range)
+    }
+
+    /// Applies the edit to the contents of a file (leaving them alone if the edit doesn't fit them)
+    #[inline]
+    pub fn apply(&self, contents: &mut String) -> Result<(), EditError> {
+        contents.replace_range(self.range(contents)?, &self.text);
+        Ok(())
+    }
+
+    /// Converts `(ln, ln_idx)` into a byte offset (the end of a line is its length plus one)
+    fn offset(contents: &str, (ln, ln_idx): (usize, usize)) -> Result<usize, EditError> {
+        let out_of_range = EditError::OutOfRange((ln, ln_idx));
+        let line_start = match ln {
+            0 => return Err(out_of_range),
+            1 => 0,
+            _ => contents.match_indices('\n').nth(ln - 2).ok_or(out_of_range)?.0 + 1,
+        };
+        let line = contents[line_start..].split('\n').next().unwrap_or_default();
+
+        match ln_idx.checked_sub(1).ok_or(out_of_range)? {
+            idx if idx == line.chars().count() => Ok(line_start + line.len()), // the end of the line
+            idx => line.char_indices().nth(idx).map(|(i, _)| line_start + i).ok_or(out_of_range),
+        }
+    }
+}
+
+/// Why a [`TextEdit`] doesn't fit the contents of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditError {
+    /// A `(ln, ln_idx)` that isn't in the file (both start at one)
+    OutOfRange((usize, usize)),
+    /// An edit that ends before it starts
+    Inverted((usize, usize), (usize, usize)),
+}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfRange((ln, ln_idx)) => write!(f, "{ln}:{ln_idx} isn't in the file"),
+            Self::Inverted(start, end) => write!(f, "edit ends at {}:{} before it starts at {}:{}", end.0, end.1, start.0, start.1),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+/// A top-level item of a [`Document`] and the tokens it was parsed from
+#[derive(Debug)]
+pub struct Item<N: Debug> {
+    pub tokens: Range<usize>,
+    pub node: Result<Node<N>, CompileError>,
+}
+
+/// A file that is re-lexed and re-parsed incrementally as it's edited
+/// - Only the tokens around an edit are re-lexed, up until the token stream lines up with the old one again
+///   (the lexer restarts with the state it had after an earlier token, and the streams only line up where its states match)
+/// - Top-level items are reused when they're before or after the edit (moving the positions of the ones after it)
+pub struct Document<TT: Display, N: Debug> {
+    pub file_name: String,
+    /// The file in the global source map (its contents are swapped in place on every edit, so positions from before an edit
+    /// point into the new contents)
+    pub file: FileId,
+    pub contents: String,
+    pub tokens: Box<[Token<TT>]>,
+    /// The state of the lexer after each token it lexed (layout and `eof` tokens don't have one)
+    states: Vec<Option<LexState>>,
+    pub items: Vec<Item<N>>,
+    /// The error the lexer threw (which leaves the document without any tokens until an edit fixes it)
+    pub lex_error: Option<CompileError>,
+    tokenize: ResumableTokenizer<TT>,
+    parse: ItemParser<TT, N>,
+}
+
+impl<TT: Display + PartialEq, N: Walk> Document<TT, N> {
+    pub fn new(file_name: String, contents: String, tokenize: ResumableTokenizer<TT>, parse: ItemParser<TT, N>) -> Self {
+        let file = source_map::add_file(file_name.clone(), contents.clone());
+        let mut out = Self { file_name, file, contents, tokens: Box::new([]), states: Vec::new(), items: Vec::new(), lex_error: None, tokenize, parse };
+        out.reload();
+        out
+    }
+
+    /// Every error of the lexer and the top-level items
+    pub fn errors(&self) -> impl Iterator<Item = &CompileError> {
+        self.lex_error.iter().chain(self.items.iter().filter_map(|x| x.node.as_ref().err()))
+    }
+
+    /// Lexes and parses the whole file again
+    fn reload(&mut self) {
+        let (tokenize, file) = (self.tokenize, self.file);
+        let mut states = Vec::new();
+        match CompileError::catch(|| tokenize(Lext::from_file(file), &mut |token, state| { states.push((token.position, state.clone())); false })) {
+            Ok(tokens) => {
+                self.states = align(&tokens, states);
+                self.tokens = tokens;
+                self.lex_error = None;
+                self.items = self.parse_items(0, Vec::new(), Vec::new());
+            },
+            Err(error) => self.lex_failed(error),
+        }
+    }
+
+    #[inline]
+    fn lex_failed(&mut self, error: CompileError) {
+        self.tokens = Box::new([]);
+        self.states = Vec::new();
+        self.items = Vec::new();
+        self.lex_error = Some(error);
+    }
+
+    /// Applies an edit and incrementally updates the tokens and items
+    /// (or lexes the whole file again if the lexer threw an error before), leaving the document alone if the edit doesn't fit it
+    pub fn edit(&mut self, edit: &TextEdit) -> Result<(), EditError> {
+        let range = edit.range(&self.contents)?;
+        let map = |offset: usize| offset + edit.text.len() - range.len(); // for offsets after the edit
+        self.contents.replace_range(range.clone(), &edit.text);
+        source_map::update_file(self.file, self.contents.clone());
+        if self.lex_error.is_some() { self.reload(); return Ok(()) }
+
+        // Re-lex from one token before the last token that (with its trailing trivia) ends before the edit,
+        // with the state the lexer had after the token before that
+        let old = std::mem::take(&mut self.tokens).into_vec();
+        let old_states = std::mem::take(&mut self.states);
+        let restart = (0..old.len())
+            .filter(|i| old_states[*i].is_some() && trailing_end(&old[*i]) < range.start)
+            .nth_back(1);
+        let (first, lext) = match restart {
+            Some(i) => {
+                let mut lext = Lext::from_file(self.file).resume(old_states[i].clone().expect("only lexed tokens are restarted from"));
+                lext.seek_to(trailing_end(&old[i]));
+                (i + 1, lext)
+            },
+            None => (0, Lext::from_file(self.file)),
+        };
+
+        let mut candidate = first;
+        let mut resync = None;
+        let mut states = Vec::new();
+        let tokenize = self.tokenize;
+        let window = CompileError::catch(|| tokenize(lext, &mut |token, state| {
+            states.push((token.position, state.clone()));
+            let start = token.position.0.offset();
+            while let Some(old_token) = old.get(candidate) {
+                let old_start = old_token.position.0.offset();
+                if old_states[candidate].is_none() || leading_start(old_token) < range.end || map(old_start) < start { candidate += 1; continue }
+                if map(old_start) == start
+                    && old_token.token_type == token.token_type
+                    && Trivia::text(&old_token.trivia.leading) == Trivia::text(&token.trivia.leading)
+                    && old_states[candidate].as_ref() == Some(state) {
+                    resync = Some(candidate);
+                    return true;
+                } break;
+            } false
+        }));
+        let window = match window {
+            Ok(window) => window,
+            Err(error) => { self.lex_failed(error); return Ok(()) },
+        };
+        let window_states = align(&window, states);
+
+        // Items after the edit are reused from the first one that starts at or after where the tokens line up again
+        let old_items = std::mem::take(&mut self.items);
+        let resync_idx = resync.unwrap_or(usize::MAX);
+        let new_resync = first + window.len();
+        let suffix_items = old_items.iter()
+            .position(|x| x.tokens.start >= resync_idx && leading_start(&old[x.tokens.start]) >= range.end)
+            .unwrap_or(old_items.len());
+        let prefix_items = old_items.iter()
+            .take_while(|x| x.tokens.end <= first)
+            .count()
+            .saturating_sub(1); // the last one might've looked ahead into the edit
+
+        // Stitch the tokens (and the lexer's states) back together
+        let (mut tokens, mut states) = (old, old_states);
+        let (suffix, suffix_states) = match resync {
+            Some(m) => (
+                tokens.drain(m..).map(|x| rebase(x, &map)).collect(),
+                states.drain(m..).map(|x| x.map(|mut x| { x.rebase(|x| rebase_position(x, &map)); x })).collect(),
+            ),
+            None => (Vec::new(), Vec::new()),
+        };
+        tokens.truncate(first);
+        tokens.extend(window.into_vec());
+        tokens.extend(suffix);
+        states.truncate(first);
+        states.extend(window_states);
+        states.extend(suffix_states);
+        self.tokens = tokens.into_boxed_slice();
+        self.states = states;
+
+        // Re-parse everything in-between the reusable items
+        let mut old_items = old_items;
+        let suffix = old_items.drain(suffix_items..)
+            .map(|x| Item {
+                tokens: x.tokens.start - resync_idx + new_resync..x.tokens.end - resync_idx + new_resync,
+                node: match x.node {
+                    Ok(node) => Ok(node.fold_with(&mut Rebase(&map))),
+                    Err(mut error) => { error.position = rebase_position(&error.position, &map); Err(error) },
+                },
+            })
+            .collect::<Vec<_>>();
+        old_items.truncate(prefix_items);
+        let from = old_items.last().map_or(0, |x| x.tokens.end);
+        self.items = self.parse_items(from, old_items, suffix);
+        Ok(())
+    }
+
+    /// Parses items from a token index until the end of the file or until it lines up with an already parsed item
+    fn parse_items(&self, from: usize, mut items: Vec<Item<N>>, suffix: Vec<Item<N>>) -> Vec<Item<N>> {
+        let mut parxt = Parxt::new(&self.tokens).with_eof(Position::eof(self.file));
+        parxt.rewind(parxt.checkpoint_at(from));
+        let mut suffix = suffix.into_iter().peekable();
+
+        while parxt.current().is_some() {
+            let idx = parxt.idx();
+            while suffix.peek().is_some_and(|x| x.tokens.start < idx) { suffix.next(); }
+            if suffix.peek().is_some_and(|x| x.tokens.start == idx) { items.extend(suffix); break }
+
+            let (tokens, node) = parse_item(&mut parxt, self.parse, |_, x| Ok(x));
+            items.push(Item { tokens, node });
+        } items
+    }
+}
+
+impl<TT: Display, N: Debug> Drop for Document<TT, N> {
+    /// Removes the document's file from the global source map
+    #[inline]
+    fn drop(&mut self) {
+        source_map::remove_file(self.file);
+    }
+}
+
+/// The byte offset of where a token's leading trivia (or the token itself) starts
+#[inline]
+fn leading_start<TT: Display>(token: &Token<TT>) -> usize {
+    token.trivia.leading.first().map_or(&token.position.0, |x| &x.position.0).offset()
+}
+
+/// The byte offset of where a token's trailing trivia (or the token itself) ends
+#[inline]
+fn trailing_end<TT: Display>(token: &Token<TT>) -> usize {
+    token.trivia.trailing.last().map_or(&token.position.1, |x| &x.position.1).offset()
+}
+
+/// Lines up the states `tokenize_from` gave with the tokens they're for (layout and `eof` tokens don't get one)
+fn align<TT: Display>(tokens: &[Token<TT>], states: Vec<(Position, LexState)>) -> Vec<Option<LexState>> {
+    let mut states = states.into_iter().peekable();
+    tokens.iter()
+        .map(|token| states.next_if(|(position, _)| !position.is_empty() && *position == token.position).map(|x| x.1))
+        .collect()
+}
+
+/// Moves a position from after an edit to where it is in the new file
+#[inline]
+fn rebase_position(position: &Position, map: &impl Fn(usize) -> usize) -> Position {
+    Position(position.0.at(map(position.0.offset())), position.1.at(map(position.1.offset())))
+}
+
+/// Moves the nodes of an item from after an edit to where they are in the new file
+struct Rebase<'a, F: Fn(usize) -> usize>(&'a F);
+
+impl<F: Fn(usize) -> usize> Fold for Rebase<'_, F> {
+    fn fold<N: Walk>(&mut self, node: Node<N>) -> Node<N> {
+        let mut node = node.fold_children(self);
+        node.position = rebase_position(&node.position, self.0);
+        node
+    }
+}
+
+/// Moves a token from after an edit to where it is in the new file
+fn rebase<TT: Display>(mut token: Token<TT>, map: &impl Fn(usize) -> usize) -> Token<TT> {
+    let rebase_pos = |position: &Position| rebase_position(position, map);
+    let rebase_trivia = |pieces: &[TriviaPiece]| pieces.iter()
+        .map(|x| TriviaPiece { position: rebase_pos(&x.position), text: x.text.clone() })
+        .collect();
+
+    token.position = rebase_pos(&token.position);
+    token.trivia.leading = rebase_trivia(&token.trivia.leading);
+    token.trivia.trailing = rebase_trivia(&token.trivia.trailing);
+    token
+}
@@ -0,0 +1,95 @@
+//! Indentation-sensitive lexing (the `layout` section of `lexer!`)
+//!
+//! A logical line starts at the first token on a line that only has whitespace before it, and its indentation is that
+//! whitespace; each logical line is ended by a `Newline` token and followed by an `Indent` token if it's indented
+//! further than the line before it, or a `Dedent` token for every level it goes back
+use std::sync::Arc;
+use crate::cursor::Position;
+
+/// A token generated from the indentation of the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutToken {
+    Newline,
+    Indent,
+    Dedent,
+}
+
+/// Tracks the indentation levels of logical lines
+/// - The levels are shared between clones (and only copied when one of them changes), so saving it is cheap
+#[derive(Debug, Clone)]
+pub struct Layout {
+    levels: Arc<Vec<String>>,
+    last: Option<Position>,
+}
+
+impl Default for Layout {
+    #[inline]
+    fn default() -> Self {
+        Self { levels: Arc::new(vec![String::new()]), last: None }
+    }
+}
+
+impl Layout {
+    /// Generates the layout tokens that go before a token (all zero-width)
+    /// - Also returns the position of the token's indentation if it's inconsistent with the lines before it
+    ///   (it doesn't match an outer level, or mixes tabs and spaces differently)
+    pub fn before(&mut self, position: &Position) -> (Vec<(LayoutToken, Position)>, Option<Position>) {
+        let start = &position.0;
+        let last = self.last.replace(*position);
+        let mut tokens = Vec::new();
+        let file = start.source_file();
+        let ln = start.location_in(&file).0;
+        if last.as_ref().is_some_and(|x| x.end().0 >= ln) { return (tokens, None) } // not the start of a line
+
+        let line_start = file.contents.offset(ln, 1);
+        let indent = file.contents.source().as_str()[line_start..start.offset()].to_string();
+        if !indent.chars().all(char::is_whitespace) { return (tokens, None) } // continues a multi-line token or trivia
+
+        if let Some(last) = last {
+            tokens.push((LayoutToken::Newline, Position::empty(&last.1)));
+        }
+
+        let here = Position::empty(start);
+        let top = self.levels.last().expect("there is always a base level");
+        if indent == *top { return (tokens, None) }
+        if indent.starts_with(top.as_str()) {
+            Arc::make_mut(&mut self.levels).push(indent);
+            tokens.push((LayoutToken::Indent, here));
+            return (tokens, None);
+        }
+
+        while self.levels.last().is_some_and(|x| x.len() > indent.len() && x.starts_with(&indent)) {
+            Arc::make_mut(&mut self.levels).pop();
+            tokens.push((LayoutToken::Dedent, here));
+        }
+
+        if self.levels.last().is_some_and(|x| *x == indent) { return (tokens, None) }
+        let error = Position(start.at(line_start), *start);
+        if self.levels.last().is_none_or(|x| indent.starts_with(x.as_str())) { Arc::make_mut(&mut self.levels).push(indent) } // carry on from the new level
+        (tokens, Some(error))
+    }
+
+    /// Checks if two layouts are at the same indentation levels (wherever their last tokens were)
+    #[inline]
+    pub(crate) fn same_levels(&self, other: &Self) -> bool {
+        self.levels == other.levels
+    }
+
+    /// Moves the position of the last token (for when it's moved by an edit before it)
+    #[inline]
+    pub(crate) fn rebase(&mut self, map: impl Fn(&Position) -> Position) {
+        self.last = self.last.as_ref().map(map);
+    }
+
+    /// Generates the layout tokens at the end of the file (ending the last line and dedenting back to the base level)
+    pub fn finish(&mut self) -> Vec<(LayoutToken, Position)> {
+        let Some(last) = self.last.take() else { return Vec::new() };
+        let here = Position::empty(&last.1);
+
+        let mut tokens = vec![(LayoutToken::Newline, here)];
+        while self.levels.len() > 1 {
+            Arc::make_mut(&mut self.levels).pop();
+            tokens.push((LayoutToken::Dedent, here));
+        } tokens
+    }
+}
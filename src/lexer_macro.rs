@@ -1,53 +1,253 @@
 /// Creates a lexer
+/// - Rules are tried in the order they're declared, except that consecutive literal rules (`Name: +;` or `Name: (= =);`) and pattern rules (`Name ~ ([alpha, '_'] [alpha, digit, '_']*);`) are matched together and the longest one wins (the first one on a tie)
+/// - Two literal rules with the same text give a deprecation warning, as only the first one can ever match (overlaps with patterns and other rules aren't checked)
+/// - Character classes can contain strings, chars, ranges (`'a'..='z'`) and the predefined classes `alpha`, `digit`, `whitespace`, `xid_start` and `xid_continue`
+/// - Patterns are made of classes (`[...]`), negated classes (`![...]`), literals (`"0x"`) and groups (`(...)`, with `|` between alternatives), each optionally followed by `*`, `+` or `?`
+/// - Rules in a `mode Name { else <expr>; <rules> };` block are only used once a rule enters the mode with `push_mode: Name;` (until `pop_mode: ();`), and `push_mode: default;` enters the rules outside of any mode
+/// - Pushing a mode that isn't declared fails to compile, and `pop_mode: ();` panics outside of any mode (use `pop_mode else { <rules> };` to handle that instead)
+/// - A `layout(Indent, Dedent, Newline) else |position| <expr>;` section (after `keywords`) inserts those token types around logical lines (see `flexar::layout`), evaluating `<expr>` with the position of any inconsistent indentation
+/// - An `eof(Eof);` section (after `layout`) adds an `Eof` token type that's always the last token, positioned at the true end of the file (with any trailing trivia as its leading trivia), so `parser!` patterns can match `(Eof)` like any other token
+/// - Attributes before the header (like `#[derive(serde::Serialize, serde::Deserialize)]`) are added to the generated token type
+/// - Repetitions in patterns are greedy and never backtrack, and the matched text is parsed into the token's field (if it has one) with `FromStr`
 #[macro_export]
 macro_rules! lexer {
-    ([[$token_type:ident] $lext:ident, $current:ident $(, $label:tt)?] else $no_match:expr; token_types {$($variant:ident$(($varin_name:ident: $varin_type:ty))? => $fmt:expr;)*} $($first:tt$sep:tt$second:tt;)*) => {
+    ($(#[$meta:meta])* [[$token_type:ident] $lext:ident, $current:ident $(, $label:tt)?] else $no_match:expr; $(keywords($keyword_of:ident) {$($keyword:ident => $keyword_lit:literal;)*})? $(layout($indent:ident, $dedent:ident, $newline:ident) else |$layout_pos:ident| $layout_err:expr;)? $(eof($eof:ident);)? token_types {$($types:tt)*} $($rules:tt)*) => {
+        $crate::lexer!(@collide [$($($keyword_lit),*)?] $($types)*);
+        $crate::lexer!(@generate [$(#[$meta])*] [[$token_type] $lext, $current $(, $label)?] else $no_match; $(keywords($keyword_of) {$($keyword => $keyword_lit;)*})? $(layout($indent, $dedent, $newline) else |$layout_pos| $layout_err;)? $(eof($eof);)? token_types {$($types)*} $($rules)*);
+    };
+
+    (@generate [$(#[$meta:meta])*] [[$token_type:ident] $lext:ident, $current:ident $(, $label:tt)?] else $no_match:expr; $(keywords($keyword_of:ident) {$($keyword:ident => $keyword_lit:literal;)*})? $(layout($indent:ident, $dedent:ident, $newline:ident) else |$layout_pos:ident| $layout_err:expr;)? $(eof($eof:ident);)? token_types {$($([$class:ident])? $variant:ident$(($varin_name:ident: $varin_type:ty))? => $fmt:expr;)*} $($first:tt$sep:tt$second:tt;)*) => {
+        const _: () = {
+            const LITERALS: &[&str] = &[$($crate::lexer!(@literal $first$sep$second)),*];
+            $($crate::lexer!(@ambiguity LITERALS $first$sep$second);)*
+        };
+
         #[derive(Debug, Clone, PartialEq)]
+        $(#[$meta])*
         pub enum $token_type {
-            $($variant$(($varin_type))?),*
+            $($variant$(($varin_type))?,)*
+            $($($keyword,)*)?
+            $($eof,)?
         }
 
         impl std::fmt::Display for $token_type {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 match self {
-                    $(Self::$variant$(($varin_name))? => write!(f, "{}", $fmt)),*
+                    $(Self::$variant$(($varin_name))? => write!(f, "{}", $fmt),)*
+                    $($(Self::$keyword => write!(f, "{}", $keyword_lit),)*)?
+                    $(Self::$eof => write!(f, "end of file"),)?
+                }
+            }
+        }
+
+        impl $crate::highlight::Classify for $token_type {
+            #[allow(unused_variables)]
+            fn kind(&self) -> $crate::highlight::TokenKind {
+                match self {
+                    $(Self::$variant$(($varin_name))? => $crate::lexer!(@kind $($class)?),)*
+                    $($(Self::$keyword => $crate::highlight::TokenKind::Keyword,)*)?
+                    $(Self::$eof => $crate::highlight::TokenKind::Other,)?
                 }
             }
         }
 
+        $(impl $token_type {
+            /// Gets the keyword an identifier is (from `keywords`)
+            #[deny(unreachable_patterns)] // keywords can't be duplicated
+            pub fn keyword(ident: &str) -> Option<Self> {
+                match ident {
+                    $($keyword_lit => Some(Self::$keyword),)*
+                    _ => None,
+                }
+            }
+
+            /// Reclassifies an identifier token into a keyword
+            #[inline]
+            fn reclassify_keyword(token: &mut $crate::token_node::Token<Self>) {
+                if let Self::$keyword_of(ident) = &token.token_type {
+                    if let Some(keyword) = Self::keyword(ident) { token.token_type = keyword }
+                }
+            }
+        })?
+
         impl $token_type {
-            pub fn tokenize(mut $lext: $crate::lext::Lext) -> Box<[$crate::token_node::Token<Self>]> {
+            /// Gets the class of the token (from `token_types`)
+            #[inline]
+            pub fn kind(&self) -> $crate::highlight::TokenKind {
+                $crate::highlight::Classify::kind(self)
+            }
+
+            #[inline]
+            pub fn tokenize($lext: $crate::lext::Lext) -> Box<[$crate::token_node::Token<Self>]> {
+                Self::tokenize_with($lext, &mut |_| false)
+            }
+
+            /// Tokenizes until `stop` returns true for a token (which is left out)
+            #[inline]
+            pub fn tokenize_with($lext: $crate::lext::Lext, stop: &mut dyn FnMut(&$crate::token_node::Token<Self>) -> bool) -> Box<[$crate::token_node::Token<Self>]> {
+                Self::tokenize_from($lext, &mut |token, _| stop(token))
+            }
+
+            /// Tokenizes until `stop` returns true for a token (which is left out), also giving it the state of the lexer
+            /// after the token (to resume from with `Lext::resume`)
+            pub fn tokenize_from(mut $lext: $crate::lext::Lext, stop: &mut dyn FnMut(&$crate::token_node::Token<Self>, &$crate::lext::LexState) -> bool) -> Box<[$crate::token_node::Token<Self>]> {
                 let mut tokens = Vec::<$crate::token_node::Token<Self>>::new();
+                let mut trivia = Vec::<$crate::token_node::TriviaPiece>::new();
+                $($crate::lexer!(@ignore $indent); let mut layout = $lext.take_layout();)?
+                #[allow(dead_code)]
+                struct LexerModes; // `push_mode` goes through its constants, so unknown modes don't compile
+                #[allow(dead_code, non_upper_case_globals)]
+                impl LexerModes { const default: &'static str = "default"; }
+                $($crate::lexer!(@declare-mode $first$sep$second);)*
                 $($label:)? while let Some($current) = $lext.current {
-                    tokens.push('code: {
-                        $($crate::lexer!(@sect $lext 'code $current $first$sep$second);)*
-                        $no_match
-                    });
-                    $lext.cursor.pos_start = $lext.cursor.pos_end.clone(); // cause different tokens with different start pos
-                } tokens.into_boxed_slice()
+                    #[allow(unreachable_code, clippy::diverging_sub_expression)] // when `$no_match` diverges
+                    let lexeme: Option<$crate::token_node::Token<Self>> = 'code: {
+                        $($crate::lexer!(@mode $lext 'code $current $first$sep$second);)*
+                        if let Some(mode) = $lext.mode() {
+                            if mode != "default" { panic!("[lexer] unknown mode `{mode}`") }
+                        }
+                        $crate::lexer!(@rules $lext 'code $current ($no_match) $($first$sep$second;)*);
+                    };
+                    match lexeme {
+                        Some(mut token) => {
+                            $($crate::lexer!(@ignore $keyword_of); Self::reclassify_keyword(&mut token);)?
+                            if !trivia.is_empty() { $crate::token_node::Trivia::attach(&mut tokens, &mut trivia, Some(&mut token)) }
+                            $(
+                                let (layout_tokens, error) = layout.before(&token.position);
+                                if let Some($layout_pos) = error { let _: () = $layout_err; }
+                                tokens.extend(layout_tokens.into_iter().map(|(x, position)| $crate::token_node::Token::new(position, $crate::lexer!(@layout x $indent $dedent $newline))));
+                            )?
+                            if stop(&token, &$lext.state(None $(.or({ $crate::lexer!(@ignore $indent); Some(&layout) }))?)) { break }
+                            tokens.push(token);
+                        },
+                        None => {
+                            let position = $lext.rposition();
+                            trivia.push($crate::token_node::TriviaPiece { text: position.text().into_boxed_str(), position });
+                        },
+                    }
+                    $lext.cursor.pos_start = $lext.cursor.pos_end; // cause different tokens with different start pos
+                }
+                #[allow(unused_mut)]
+                let mut eof: Option<$crate::token_node::Token<Self>> = None;
+                $(if $lext.current.is_none() { eof = Some($crate::token_node::Token::new($lext.eof(), Self::$eof)) })? // not stopped early
+                if !trivia.is_empty() { $crate::token_node::Trivia::attach(&mut tokens, &mut trivia, eof.as_mut()) }
+                $(if $lext.current.is_none() { // not stopped early
+                    tokens.extend(layout.finish().into_iter().map(|(x, position)| $crate::token_node::Token::new(position, $crate::lexer!(@layout x $indent $dedent $newline))));
+                })?
+                tokens.extend(eof);
+                tokens.into_boxed_slice()
             }
         }
     };
     
+    (@ignore $($tt:tt)*) => {};
+
+    (@rules $lext:ident $label:tt $current:ident ($no_match:expr) $($first:tt$sep:tt$second:tt;)*) => {
+        #[allow(unused_mut, unused_variables)]
+        let mut longest: Option<(usize, $crate::cursor::Cursor, Option<char>, Self)> = None; // (length, end, current, token type)
+        #[allow(unused_mut, unused_variables)]
+        let mut run = $lext.spawn(); // respawned for every literal and pattern rule instead of spawning a lexer for each one
+        $($crate::lexer!(@sect $lext $label $current run longest $first$sep$second);)*
+        $crate::lexer!(@flush $lext $label longest);
+        break $label Some($no_match);
+    };
+
+    // Ends a run of literal and pattern rules with the longest match of the run (if there is one)
+    (@flush $lext:ident $label:tt $longest:ident) => {
+        if let Some((_, end, current, token_type)) = $longest.take() {
+            $lext.cursor.pos_start = $lext.cursor.pos_end;
+            $lext.jump(end, current);
+            break $label Some($crate::token_node::Token::new($lext.rposition(), token_type));
+        }
+    };
+
+    // Layout
+
+    (@layout $token:ident $indent:ident $dedent:ident $newline:ident) => {
+        match $token {
+            $crate::layout::LayoutToken::Indent => Self::$indent,
+            $crate::layout::LayoutToken::Dedent => Self::$dedent,
+            $crate::layout::LayoutToken::Newline => Self::$newline,
+        }
+    };
+
+    // Modes
+
+    (@mode $lext:ident $label:tt $current:ident mode $name:ident {else $no_match:expr; $($rules:tt)*}) => {
+        if $lext.mode() == Some(stringify!($name)) {
+            $crate::lexer!(@rules $lext $label $current ($no_match) $($rules)*);
+        }
+    };
+
+    (@mode $lext:ident $label:tt $current:ident mode $($invalid:tt)*) => {
+        compile_error!("[lexer] invalid mode, expected `mode Name { else <expr>; <rules> };`")
+    };
+
+    (@mode $($rule:tt)*) => {};
+
+    (@declare-mode mode $name:ident $rules:tt) => {
+        #[allow(dead_code, non_upper_case_globals)]
+        impl LexerModes { const $name: &'static str = stringify!($name); }
+    };
+
+    (@declare-mode $($rule:tt)*) => {};
+
+    // Keywords
+
+    (@collide [] $($tail:tt)*) => {};
+
+    (@collide $keywords:tt $([$class:ident])? $variant:ident$(($varin_name:ident: $varin_type:ty))? => $lit:literal; $($tail:tt)*) => {
+        $crate::lexer!(@collide-check $keywords $lit);
+        $crate::lexer!(@collide $keywords $($tail)*);
+    };
+
+    (@collide $keywords:tt $([$class:ident])? $variant:ident$(($varin_name:ident: $varin_type:ty))? => $fmt:expr; $($tail:tt)*) => {
+        $crate::lexer!(@collide $keywords $($tail)*);
+    };
+
+    (@collide $keywords:tt) => {};
+
+    (@collide-check [$($keyword:literal),*] $lit:literal) => {
+        const _: () = {
+            #[deny(unreachable_patterns)] // a keyword collides with the literal of a token
+            fn keyword_collides_with_token_literal(ident: &str) {
+                match ident {
+                    $($keyword => (),)*
+                    $lit => (),
+                    _ => (),
+                }
+            }
+        };
+    };
+
+    // Token classes
+
+    (@kind) => { $crate::highlight::TokenKind::Other };
+    (@kind keyword) => { $crate::highlight::TokenKind::Keyword };
+    (@kind operator) => { $crate::highlight::TokenKind::Operator };
+    (@kind number) => { $crate::highlight::TokenKind::Number };
+    (@kind string) => { $crate::highlight::TokenKind::String };
+    (@kind comment) => { $crate::highlight::TokenKind::Comment };
+    (@kind identifier) => { $crate::highlight::TokenKind::Identifier };
+    (@kind punctuation) => { $crate::highlight::TokenKind::Punctuation };
+    (@kind $invalid:ident) => {
+        compile_error!(concat!("[lexer] invalid token class `", stringify!($invalid), "`, expected one of `keyword`, `operator`, `number`, `string`, `comment`, `identifier` or `punctuation`"))
+    };
+
     // Sections
     
-    (@sect $lext:ident $label:tt $current:ident $out:ident: ($($tail:tt)*)) => {{ // Change to something more efficient if too slow
-        let mut child = $lext.spawn();
-        $crate::lexer!(@recur-sect1 $label $out $lext child $($tail)*);
-    }};
+    (@sect $lext:ident $label:tt $current:ident $run:ident $longest:ident $name:ident: $char:tt) => {
+        $crate::lexer!(@longest $lext $run $longest $name: $char);
+    };
 
-    (@sect $lext:ident $label:tt $current:ident $name:ident: $char:tt) => {
-        if $crate::lexer!(@value $current $char) {
-            use $crate::flext::Flext;
-            $lext.advance();
-            break $label $crate::token_node::Token {
-                position: $lext.rposition(),
-                token_type: Self::$name,
-            };
-        }
+    (@sect $lext:ident $label:tt $current:ident $run:ident $longest:ident $name:ident ~ $pattern:tt) => {
+        $crate::lexer!(@longest $lext $run $longest $name ~ $pattern);
     };
 
-    (@sect $lext:ident $label:tt $current:ident $start:tt $child:ident {$($($code:block)? $($key:ident $param:tt $body:tt)?;)*}) => {
+    (@sect $lext:ident $label:tt $current:ident $run:ident $longest:ident mode $name:ident $rules:tt) => {}; // modes are dispatched beforehand
+
+    (@sect $lext:ident $label:tt $current:ident $run:ident $longest:ident $start:tt $child:ident {$($($code:block)? $($key:ident $param:tt $body:tt)?;)*}) => {
+        $crate::lexer!(@flush $lext $label $longest);
         if $crate::lexer!(@value $current $start) {
             use $crate::flext::Flext;
             let mut $child = $lext.spawn();
@@ -58,36 +258,190 @@ macro_rules! lexer {
         }
     };
 
-    (@sect $lext:ident $label:tt $current:ident $char:tt >> ($action:expr)) => {
+    (@sect $lext:ident $label:tt $current:ident $run:ident $longest:ident $char:tt >> trivia) => {
+        $crate::lexer!(@flush $lext $label $longest);
+        if $crate::lexer!(@value $current $char) {
+            use $crate::flext::Flext;
+            $lext.advance();
+            while let Some(current) = $lext.current {
+                if !$crate::lexer!(@value current $char) { break }
+                $lext.advance();
+            }
+            break $label None;
+        }
+    };
+
+    (@sect $lext:ident $label:tt $current:ident $run:ident $longest:ident $char:tt >> ($action:expr)) => {
+        $crate::lexer!(@flush $lext $label $longest);
         if $crate::lexer!(@value $current $char) {
             $action;
         }
     };
 
-    // Recur
+    // Literals (longest match)
 
-        // For section 1
-        (@recur-sect1 $label:tt $out:ident $lext:ident $child:ident $char:tt) => {
-            if let Some(current) = $child.current {
-                if $crate::lexer!(@value current $char) {
-                    $lext = $child.clone();
-                    $lext.advance();
-                    break $label $crate::token_node::Token {
-                        position: $lext.rposition(),
-                        token_type: Self::$out,
-                    };
-                }
+    (@longest $lext:ident $child:ident $longest:ident $name:ident: ($($tail:tt)*)) => {
+        'rule: {
+            use $crate::flext::Flext;
+            let len = [$(stringify!($tail)),*].len();
+            if $longest.as_ref().is_some_and(|x| len <= x.0) { break 'rule }
+            $child.respawn(&$lext);
+            $(match $child.current {
+                Some(current) if $crate::lexer!(@value current $tail) => $child.advance(),
+                _ => break 'rule,
+            })*
+            $longest = Some((len, $child.cursor.pos_end, $child.current, Self::$name));
+        }
+    };
+
+    (@longest $lext:ident $child:ident $longest:ident $name:ident: $char:tt) => {
+        $crate::lexer!(@longest $lext $child $longest $name: ($char));
+    };
+
+    (@longest $lext:ident $child:ident $longest:ident $name:ident ~ ($($pattern:tt)*)) => {
+        'rule: {
+            use $crate::flext::Flext;
+            $child.respawn(&$lext);
+            let mut len = 0usize;
+            $crate::lexer!(@pattern $child len 'rule $($pattern)*);
+            if len == 0 || $longest.as_ref().is_some_and(|x| len <= x.0) { break 'rule }
+            let Some(token_type) = $crate::lexer_macro::FromLexeme::build(Self::$name, &$child.rposition().text()) else { break 'rule };
+            $longest = Some((len, $child.cursor.pos_end, $child.current, token_type));
+        }
+    };
+
+    (@ambiguity $literals:ident $name:ident: $literal:tt) => {{
+        struct Ambiguity<const AMBIGUOUS: bool>;
+        #[allow(dead_code)]
+        impl Ambiguity<true> {
+            #[deprecated = concat!("the literal rule `", stringify!($name), "` matches the same text (`", $crate::lexer!(@literal $name: $literal), "`) as another literal rule, so only the first of them will ever be used")]
+            const fn check() {}
+        }
+        impl Ambiguity<false> {
+            const fn check() {}
+        }
+        Ambiguity::<{ $crate::lexer_macro::count($literals, $crate::lexer!(@literal $name: $literal)) > 1 }>::check();
+    }};
+
+    (@ambiguity $literals:ident $($rule:tt)*) => {};
+
+    (@literal $name:ident: ($($tail:tt)*)) => { concat!($($crate::lexer!(@lit $tail)),*) };
+    (@literal $name:ident: $char:tt) => { concat!($crate::lexer!(@lit $char)) };
+    (@literal $($rule:tt)*) => { "" };
+
+    (@lit [$val:literal]) => { concat!("[", $val, "]") };
+    (@lit $val:literal) => { $val };
+    (@lit $val:tt) => { stringify!($val) };
+
+    // Patterns
+
+    (@pattern $lext:ident $len:ident $fail:tt) => {};
+
+    (@pattern $lext:ident $len:ident $fail:tt ! $class:tt $($tail:tt)*) => {
+        $crate::lexer!(@pattern-rep $lext $len $fail (!$class) $($tail)*);
+    };
+
+    (@pattern $lext:ident $len:ident $fail:tt $elem:tt $($tail:tt)*) => {
+        $crate::lexer!(@pattern-rep $lext $len $fail ($elem) $($tail)*);
+    };
+
+    (@pattern-rep $lext:ident $len:ident $fail:tt $elem:tt * $($tail:tt)*) => {
+        'rep: loop {
+            let (end, current, len) = ($lext.cursor.pos_end, $lext.current, $len); // to go back to if the element fails
+            'elem: {
+                $crate::lexer!(@pattern-elem $lext $len 'elem $elem);
+                if $len > len { continue 'rep }
             }
-        };
+            $lext.jump(end, current);
+            $len = len;
+            break
+        }
+        $crate::lexer!(@pattern $lext $len $fail $($tail)*);
+    };
 
-        (@recur-sect1 $label:tt $out:ident $lext:ident $child:ident $char:tt $($tail:tt)*) => {
-            if let Some(current) = $child.current {
-                if $crate::lexer!(@value current $char) {
-                    $child.advance();
-                    $crate::lexer!(@recur-sect1 $label $out $lext $child $($tail)*);
-                }
+    (@pattern-rep $lext:ident $len:ident $fail:tt $elem:tt + $($tail:tt)*) => {
+        $crate::lexer!(@pattern-elem $lext $len $fail $elem);
+        $crate::lexer!(@pattern-rep $lext $len $fail $elem * $($tail)*);
+    };
+
+    (@pattern-rep $lext:ident $len:ident $fail:tt $elem:tt ? $($tail:tt)*) => {
+        'opt: {
+            let (end, current, len) = ($lext.cursor.pos_end, $lext.current, $len);
+            'elem: {
+                $crate::lexer!(@pattern-elem $lext $len 'elem $elem);
+                break 'opt;
             }
-        };
+            $lext.jump(end, current);
+            $len = len;
+        }
+        $crate::lexer!(@pattern $lext $len $fail $($tail)*);
+    };
+
+    (@pattern-rep $lext:ident $len:ident $fail:tt $elem:tt $($tail:tt)*) => {
+        $crate::lexer!(@pattern-elem $lext $len $fail $elem);
+        $crate::lexer!(@pattern $lext $len $fail $($tail)*);
+    };
+
+    (@pattern-elem $lext:ident $len:ident $fail:tt ([$($class:tt)*])) => {
+        match $lext.current {
+            Some(current) if $crate::lexer!(@class current $($class)*) => { $lext.advance(); $len += 1 },
+            _ => break $fail,
+        }
+    };
+
+    (@pattern-elem $lext:ident $len:ident $fail:tt (![$($class:tt)*])) => {
+        match $lext.current {
+            Some(current) if !$crate::lexer!(@class current $($class)*) => { $lext.advance(); $len += 1 },
+            _ => break $fail,
+        }
+    };
+
+    (@pattern-elem $lext:ident $len:ident $fail:tt (($($group:tt)*))) => {
+        $crate::lexer!(@pattern-alt $lext $len $fail [] [] $($group)*);
+    };
+
+    (@pattern-elem $lext:ident $len:ident $fail:tt ($val:literal)) => {
+        for x in concat!($val).chars() {
+            if $lext.current != Some(x) { break $fail }
+            $lext.advance();
+            $len += 1;
+        }
+    };
+
+    (@pattern-elem $lext:ident $len:ident $fail:tt ($($invalid:tt)*)) => {
+        compile_error!(concat!("[lexer] invalid pattern element `", stringify!($($invalid)*), "`, expected a class (`[...]` or `![...]`), a literal or a group (`(...)`)"))
+    };
+
+    (@pattern-alt $lext:ident $len:ident $fail:tt [$($alt:tt)*] [$($alts:tt)*] | $($tail:tt)*) => {
+        $crate::lexer!(@pattern-alt $lext $len $fail [] [$($alts)* [$($alt)*]] $($tail)*);
+    };
+
+    (@pattern-alt $lext:ident $len:ident $fail:tt [$($alt:tt)*] [$($alts:tt)*] $next:tt $($tail:tt)*) => {
+        $crate::lexer!(@pattern-alt $lext $len $fail [$($alt)* $next] [$($alts)*] $($tail)*);
+    };
+
+    (@pattern-alt $lext:ident $len:ident $fail:tt [$($alt:tt)*] []) => {
+        $crate::lexer!(@pattern $lext $len $fail $($alt)*);
+    };
+
+    (@pattern-alt $lext:ident $len:ident $fail:tt [$($last:tt)*] [$([$($alt:tt)*])*]) => {
+        'alt: { // the first alternative that matches wins
+            let (end, current, len) = ($lext.cursor.pos_end, $lext.current, $len);
+            $('option: {
+                $crate::lexer!(@pattern $lext $len 'option $($alt)*);
+                break 'alt;
+            }
+            $lext.jump(end, current);
+            $len = len;)*
+            'option: {
+                $crate::lexer!(@pattern $lext $len 'option $($last)*);
+                break 'alt;
+            }
+            $lext.jump(end, current);
+            $len = len;
+            break $fail;
+        }
+    };
 
     // Detailed
 
@@ -122,16 +476,65 @@ macro_rules! lexer {
 
     (@det $child:ident $lext:ident $label:tt done $var:ident ($($spec:expr)?)) => {
         $lext = $child.clone();
-        break $label $crate::token_node::Token {
-            position: $lext.rposition(),
-            token_type: Self::$var$(($spec))?,
-        };
+        break $label Some($crate::token_node::Token::new($lext.rposition(), Self::$var$(($spec))?));
+    };
+
+    (@det $child:ident $lext:ident $label:tt trivia: ()) => {
+        $lext = $child.clone();
+        break $label None;
     };
 
     (@det $child:ident $lext:ident $label:tt update: ()) => {
         $lext = $child.clone();
     };
 
+    (@det $child:ident $lext:ident $label:tt peek $var:ident ($n:expr)) => {
+        let $var = $child.peek($n).unwrap_or(' ');
+    };
+
+    (@det $child:ident $lext:ident $label:tt starts_with ($text:expr) {$($($code:block)? $($key:ident $param:tt $body:tt)?;)*}) => {
+        if $child.starts_with($text) {
+            $(
+                $($crate::lexer!(@det $child $lext $label $key $param $body);)?
+                $($code;)?
+            )*
+        }
+    };
+
+    (@det $child:ident $lext:ident $label:tt eat ($text:expr) {$($($code:block)? $($key:ident $param:tt $body:tt)?;)*}) => {
+        if $child.eat($text) {
+            $(
+                $($crate::lexer!(@det $child $lext $label $key $param $body);)?
+                $($code;)?
+            )*
+        }
+    };
+
+    (@det $child:ident $lext:ident $label:tt eat_while $var:ident [$($class:tt)*]) => {
+        let mut $var = $child.eat_while(|current| $crate::lexer!(@class current $($class)*));
+    };
+
+    (@det $child:ident $lext:ident $label:tt eat_while $var:ident ($predicate:expr)) => {
+        let mut $var = $child.eat_while($predicate);
+    };
+
+    (@det $child:ident $lext:ident $label:tt push_mode: $mode:ident) => {
+        $child.push_mode(LexerModes::$mode);
+    };
+
+    (@det $child:ident $lext:ident $label:tt pop_mode: ()) => {
+        if $child.pop_mode().is_none() { panic!("[lexer] `pop_mode` outside of any mode (use `pop_mode else {{ ... }}` to handle it)") }
+    };
+
+    (@det $child:ident $lext:ident $label:tt pop_mode else {$($($code:block)? $($key:ident $param:tt $body:tt)?;)*}) => {
+        if $child.pop_mode().is_none() {
+            $(
+                $($crate::lexer!(@det $child $lext $label $key $param $body);)?
+                $($code;)?
+            )*
+        }
+    };
+
     (@det $child:ident $lext:ident $label:tt advance: $current:ident) => {
         $child.advance();
         let $current = $child.current.unwrap_or(' ');
@@ -189,8 +592,8 @@ macro_rules! lexer {
 
     // Values
 
-    (@value $current:ident [$val:literal]) => {
-        $val.contains($current)
+    (@value $current:ident [$($class:tt)*]) => {
+        $crate::lexer!(@class $current $($class)*)
     };
 
     (@value $current:ident $val:literal) => {
@@ -198,6 +601,112 @@ macro_rules! lexer {
     };
 
     (@value $current:ident $val:tt) => {
-        stringify!($val).contains($current)
+        const { $crate::lexer_macro::CharSet::new(stringify!($val)) }.contains($current)
     };
-}
\ No newline at end of file
+
+    // Character classes
+
+    (@class $current:ident) => { false };
+
+    (@class $current:ident $start:literal ..= $end:literal $(, $($tail:tt)*)?) => {
+        $crate::lexer_macro::in_range($current, $start, $end) || $crate::lexer!(@class $current $($($tail)*)?)
+    };
+
+    (@class $current:ident $val:literal $(, $($tail:tt)*)?) => {
+        const { $crate::lexer_macro::CharSet::new(concat!($val)) }.contains($current) || $crate::lexer!(@class $current $($($tail)*)?)
+    };
+
+    (@class $current:ident $class:ident $(, $($tail:tt)*)?) => {
+        $crate::lexer!(@predefined $current $class) || $crate::lexer!(@class $current $($($tail)*)?)
+    };
+
+    (@predefined $current:ident alpha) => { $current.is_alphabetic() };
+    (@predefined $current:ident digit) => { $current.is_ascii_digit() };
+    (@predefined $current:ident whitespace) => { $current.is_whitespace() };
+    (@predefined $current:ident xid_start) => { $crate::lexer_macro::is_xid_start($current) };
+    (@predefined $current:ident xid_continue) => { $crate::lexer_macro::is_xid_continue($current) };
+    (@predefined $current:ident $invalid:ident) => {
+        compile_error!(concat!("[lexer] invalid character class `", stringify!($invalid), "`, expected one of `alpha`, `digit`, `whitespace`, `xid_start` or `xid_continue`"))
+    };
+}
+
+#[doc(hidden)]
+pub use unicode_ident::{is_xid_start, is_xid_continue};
+
+/// Checks if a character is in an inclusive range (used by the `lexer!` macro)
+#[doc(hidden)]
+#[inline]
+pub fn in_range(x: char, start: char, end: char) -> bool {
+    start <= x && x <= end
+}
+
+/// A set of characters with a bitmap for ascii (used by the `lexer!` macro)
+#[doc(hidden)]
+pub struct CharSet {
+    ascii: u128,
+    rest: &'static str,
+}
+
+impl CharSet {
+    pub const fn new(chars: &'static str) -> Self {
+        let bytes = chars.as_bytes();
+        let mut ascii = 0u128;
+        let mut rest = "";
+
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] < 128 { ascii |= 1 << bytes[i] }
+            else { rest = chars }
+            i += 1;
+        }
+        Self { ascii, rest }
+    }
+
+    #[inline]
+    pub fn contains(&self, x: char) -> bool {
+        if x.is_ascii() { self.ascii >> x as u32 & 1 == 1 }
+        else { self.rest.contains(x) }
+    }
+}
+
+/// Builds a token type from the text of a pattern rule (used by the `lexer!` macro)
+/// - Unit variants are used as-is and variants with a field parse the text into it (`None` if it can't be)
+#[doc(hidden)]
+pub trait FromLexeme<TT, Marker> {
+    fn build(self, text: &str) -> Option<TT>;
+}
+
+impl<TT> FromLexeme<TT, ()> for TT {
+    #[inline]
+    fn build(self, _: &str) -> Option<TT> {
+        Some(self)
+    }
+}
+
+impl<TT, T: std::str::FromStr, F: Fn(T) -> TT> FromLexeme<TT, (T,)> for F {
+    #[inline]
+    fn build(self, text: &str) -> Option<TT> {
+        text.parse().ok().map(self)
+    }
+}
+
+/// Counts how many times a literal appears in a list of literals (used by the `lexer!` macro to warn about ambiguous rules)
+#[doc(hidden)]
+pub const fn count(literals: &[&str], literal: &str) -> usize {
+    let (mut i, mut count) = (0, 0);
+    while i < literals.len() {
+        if str_eq(literals[i], literal) { count += 1 }
+        i += 1;
+    } count
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() { return false }
+
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] { return false }
+        i += 1;
+    } true
+}
@@ -0,0 +1,288 @@
+//! Reusable recognisers for common tokens (numbers, strings and comments) to use in `lexer!` rules
+//!
+//! Each recogniser starts on the current character of the `Lext` and leaves it on the first character after what it matched
+//! ```ignore
+//! [digit] child {
+//!     set number { lexers::number(&mut child).unwrap_or_else(|x| CompileError::from(x).throw()) };
+//!     done Number(number);
+//! };
+//! ```
+use std::fmt::Display;
+use crate::{compile_error::CompileError, cursor::{Cursor, Position}, flext::Flext, lext::Lext};
+
+/// A number literal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Int(u64),
+    Float(f64),
+}
+
+impl Number {
+    #[inline]
+    pub fn is_float(&self) -> bool {
+        matches!(self, Self::Float(_))
+    }
+
+    #[inline]
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            Self::Int(x) => x as f64,
+            Self::Float(x) => x,
+        }
+    }
+
+    /// Gets the number as an integer (truncating floats)
+    #[inline]
+    pub fn as_u64(&self) -> u64 {
+        match *self {
+            Self::Int(x) => x,
+            Self::Float(x) => x as u64,
+        }
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(x) => write!(f, "{x}"),
+            Self::Float(x) => write!(f, "{x}"),
+        }
+    }
+}
+
+/// The kind of error a recogniser ran into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LexErrorKind {
+    /// A number without any digits (after its `0x`, `0o` or `0b` prefix)
+    MissingDigits,
+    /// A digit that's too big for the radix of a number (`0b12`)
+    InvalidDigit(char),
+    /// An integer that doesn't fit in a `u64`
+    Overflow,
+    /// An exponent without any digits (`1e+`)
+    MissingExponent,
+    /// A string without a closing quote
+    UnclosedString,
+    /// An unknown or malformed escape sequence (`\q` or `\u{110000}`)
+    InvalidEscape,
+    /// A block comment without a closing delimiter
+    UnclosedComment,
+}
+
+impl LexErrorKind {
+    #[inline]
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::MissingDigits => "LX001",
+            Self::InvalidDigit(_) => "LX002",
+            Self::Overflow => "LX003",
+            Self::MissingExponent => "LX004",
+            Self::UnclosedString => "LX005",
+            Self::InvalidEscape => "LX006",
+            Self::UnclosedComment => "LX007",
+        }
+    }
+
+    #[inline]
+    pub fn error_type(&self) -> &'static str {
+        match self {
+            Self::MissingDigits => "missing digits",
+            Self::InvalidDigit(_) => "invalid digit",
+            Self::Overflow => "integer too large",
+            Self::MissingExponent => "missing exponent",
+            Self::UnclosedString => "string not closed",
+            Self::InvalidEscape => "invalid escape sequence",
+            Self::UnclosedComment => "comment not closed",
+        }
+    }
+}
+
+impl Display for LexErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingDigits => write!(f, "expected digits in number"),
+            Self::InvalidDigit(x) => write!(f, "`{x}` isn't a valid digit for the number"),
+            Self::Overflow => write!(f, "integer doesn't fit in 64 bits"),
+            Self::MissingExponent => write!(f, "expected digits in exponent"),
+            Self::UnclosedString => write!(f, "expected a quote to close string"),
+            Self::InvalidEscape => write!(f, "expected one of `\\n`, `\\r`, `\\t`, `\\0`, `\\\\`, `\\'`, `\\\"`, `\\x..` or `\\u{{..}}`"),
+            Self::UnclosedComment => write!(f, "expected a delimiter to close comment"),
+        }
+    }
+}
+
+/// An error from a recogniser
+#[derive(Debug, Clone)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub position: Position,
+}
+
+impl LexError {
+    #[inline]
+    pub fn new(kind: LexErrorKind, position: Position) -> Self {
+        Self { kind, position }
+    }
+}
+
+impl From<LexError> for CompileError {
+    #[inline]
+    fn from(error: LexError) -> Self {
+        CompileError::new(error.kind.id(), error.kind.error_type(), error.kind.to_string(), error.position)
+    }
+}
+
+/// Recognises an integer or float literal
+/// - Integers can have a `0x`, `0o` or `0b` prefix, and floats can have an exponent (`1.5e-3`)
+/// - Underscores in-between digits are ignored
+pub fn number(lext: &mut Lext) -> Result<Number, LexError> {
+    use LexErrorKind as E;
+    let start = lext.cursor.pos_end;
+    let radix = match (lext.current, lext.peek(1)) {
+        (Some('0'), Some('x' | 'X')) => 16,
+        (Some('0'), Some('o' | 'O')) => 8,
+        (Some('0'), Some('b' | 'B')) => 2,
+        _ => 10,
+    };
+    if radix != 10 { lext.advance(); lext.advance(); }
+
+    let mut digits = String::new();
+    digits_into(lext, radix, &mut digits)?;
+    if digits.is_empty() {
+        let position = if radix == 10 { lext.spawn().position() } else { since(&start, lext) };
+        return Err(LexError::new(E::MissingDigits, position));
+    }
+
+    if radix == 10 {
+        let mut float = false;
+        if lext.current == Some('.') && lext.peek(1).is_some_and(|x| x.is_ascii_digit()) { // so `1..2` and `1.max()` still work
+            float = true;
+            digits.push('.');
+            lext.advance();
+            digits_into(lext, 10, &mut digits)?;
+        }
+
+        if let Some('e' | 'E') = lext.current {
+            let exponent = lext.cursor.pos_end;
+            float = true;
+            digits.push('e');
+            lext.advance();
+            if let Some(sign @ ('+' | '-')) = lext.current { digits.push(sign); lext.advance(); }
+
+            let len = digits.len();
+            digits_into(lext, 10, &mut digits)?;
+            if digits.len() == len { return Err(LexError::new(E::MissingExponent, since(&exponent, lext))) }
+        }
+
+        if float { return Ok(Number::Float(digits.parse().expect("float literals are always valid"))) }
+    }
+
+    u64::from_str_radix(&digits, radix)
+        .map(Number::Int)
+        .map_err(|_| LexError::new(E::Overflow, since(&start, lext)))
+}
+
+/// Recognises a string surrounded by `quote` (starting on the opening quote)
+/// - Supports the escape sequences `\n`, `\r`, `\t`, `\0`, `\\`, `\'`, `\"`, `\x7f` (ascii) and `\u{1F600}`
+pub fn string(lext: &mut Lext, quote: char) -> Result<String, LexError> {
+    let start = lext.spawn().position();
+    lext.advance();
+
+    let mut string = String::new();
+    loop {
+        match lext.current {
+            None => return Err(LexError::new(LexErrorKind::UnclosedString, start)),
+            Some(x) if x == quote => { lext.advance(); return Ok(string) },
+            Some('\\') => string.push(escape(lext)?),
+            Some(x) => { string.push(x); lext.advance() },
+        }
+    }
+}
+
+/// Recognises a line comment starting with `prefix`, up to (but not including) the newline
+/// - Returns `false` (without moving) if there isn't one
+pub fn line_comment(lext: &mut Lext, prefix: &str) -> bool {
+    if !lext.eat(prefix) { return false }
+    lext.eat_while(|x| x != '\n');
+    true
+}
+
+/// Recognises a block comment between `open` and `close`, which can contain other block comments if `nested` is true
+/// - Returns `Ok(false)` (without moving) if there isn't one
+pub fn block_comment(lext: &mut Lext, open: &str, close: &str, nested: bool) -> Result<bool, LexError> {
+    let start = lext.cursor.pos_end;
+    if !lext.eat(open) { return Ok(false) }
+    let opening = since(&start, lext);
+
+    let mut depth = 1;
+    while depth > 0 {
+        if lext.eat(close) { depth -= 1 }
+        else if nested && lext.eat(open) { depth += 1 }
+        else if lext.current.is_some() { lext.advance() }
+        else { return Err(LexError::new(LexErrorKind::UnclosedComment, opening)) }
+    } Ok(true)
+}
+
+/// Recognises an escape sequence (starting on the backslash)
+fn escape(lext: &mut Lext) -> Result<char, LexError> {
+    let start = lext.cursor.pos_end;
+    let escaped = escaped(lext);
+    if lext.current.is_some() { lext.advance() }
+    escaped.ok_or_else(|| LexError::new(LexErrorKind::InvalidEscape, since(&start, lext)))
+}
+
+/// Reads an escape sequence, leaving the lexer on its last character (or the first invalid one)
+fn escaped(lext: &mut Lext) -> Option<char> {
+    lext.advance();
+    Some(match lext.current? {
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        '0' => '\0',
+        '\\' => '\\',
+        '\'' => '\'',
+        '"' => '"',
+        'x' => {
+            let mut value = 0;
+            for _ in 0..2 {
+                lext.advance();
+                value = value * 16 + lext.current?.to_digit(16)?;
+            }
+            if value > 0x7f { return None }
+            char::from_u32(value)?
+        },
+        'u' => {
+            lext.advance();
+            if lext.current? != '{' { return None }
+
+            let (mut value, mut len) = (0, 0);
+            loop {
+                lext.advance();
+                match lext.current? {
+                    '}' if len > 0 => break,
+                    x if len < 6 => value = value * 16 + x.to_digit(16)?,
+                    _ => return None,
+                }
+                len += 1;
+            }
+            char::from_u32(value)?
+        },
+        _ => return None,
+    })
+}
+
+/// Reads digits of a radix (skipping underscores)
+fn digits_into(lext: &mut Lext, radix: u32, digits: &mut String) -> Result<(), LexError> {
+    while let Some(x) = lext.current {
+        if x.is_digit(radix) { digits.push(x) }
+        else if x.is_ascii_digit() { return Err(LexError::new(LexErrorKind::InvalidDigit(x), lext.spawn().position())) }
+        else if x != '_' { break }
+        lext.advance();
+    } Ok(())
+}
+
+/// The position from a cursor up to the last character the lexer moved past
+#[inline]
+fn since(start: &Cursor, lext: &Lext) -> Position {
+    Position(*start, lext.rposition().1)
+}
@@ -1,29 +1,226 @@
-use crate::{cursor::{MutCursor, Cursor}, flext::Flext};
+use std::{io::{self, Read}, string::FromUtf8Error, sync::{atomic::{AtomicUsize, Ordering}, Arc}};
+use crate::{cursor::{MutCursor, Cursor, Position, Source}, flext::Flext, layout::Layout, source_map::{self, FileId}};
+
+/// The amount of virtual files created (for their names)
+static VIRTUAL_FILES: AtomicUsize = AtomicUsize::new(0);
 
 /// Lexer context for tokenising
 #[derive(Debug, Clone)]
 pub struct Lext {
     pub cursor: MutCursor,
     pub current: Option<char>,
+    /// The stack of lexer modes (the rules outside of any `mode` are used when it's empty), shared with spawned lexers
+    modes: Option<Arc<Mode>>,
+    /// The layout to resume from (taken by `tokenize_from` when it starts)
+    layout: Option<Layout>,
+}
+
+/// What a lexer carries from one token to the next besides where it is (its mode stack and layout levels),
+/// so lexing can pick up again from the middle of a file with `Lext::resume`
+#[derive(Debug, Clone, Default)]
+pub struct LexState {
+    modes: Option<Arc<Mode>>,
+    layout: Option<Layout>,
+}
+
+impl LexState {
+    /// Gets the names of the modes on the stack (innermost first)
+    pub fn modes(&self) -> impl Iterator<Item = &'static str> + '_ {
+        std::iter::successors(self.modes.as_deref(), |x| x.prev.as_deref()).map(|x| x.name)
+    }
+
+    /// Moves the position of the last token the layout saw (for when it's moved by an edit before it)
+    #[inline]
+    pub(crate) fn rebase(&mut self, map: impl Fn(&Position) -> Position) {
+        if let Some(layout) = &mut self.layout { layout.rebase(map) }
+    }
+}
+
+impl PartialEq for LexState {
+    /// States are equal when they lex the rest of a file the same way (the same modes and indentation levels)
+    fn eq(&self, other: &Self) -> bool {
+        let same_layout = match (&self.layout, &other.layout) {
+            (Some(x), Some(y)) => x.same_levels(y),
+            (x, y) => x.is_none() && y.is_none(),
+        };
+        same_layout && self.modes().eq(other.modes())
+    }
+}
+
+/// A lexer mode and the one it goes back to
+#[derive(Debug)]
+struct Mode {
+    name: &'static str,
+    prev: Option<Arc<Mode>>,
 }
 
 impl Lext {
+    /// Creates a lexer over a copy of the contents of a file
+    /// (added to the global source map, where it stays until it's removed with `source_map::remove_file`)
     #[inline]
     pub fn new(file_name: String, contents: &str) -> Self {
-        let cursor = MutCursor::new(Cursor::new(file_name, contents.trim_start_matches('\n')));
-        let current = cursor.pos_end.get_char();
+        Self::from_source(file_name, contents.to_string())
+    }
+
+    /// Creates a lexer over a source without copying it (a `&'static str`, `Arc<str>`, `String` or `Box<str>`)
+    pub fn from_source(file_name: String, source: impl Into<Source>) -> Self {
+        Self::from_file(source_map::add_file(file_name, source))
+    }
+
+    /// Creates a lexer over a file that's already in the global source map
+    pub fn from_file(file: FileId) -> Self {
+        let cursor = MutCursor::new(Cursor::from_file(file));
+        let current = cursor.pos_end.get_char_in(&cursor.file);
         Self {
             cursor,
             current,
+            modes: None,
+            layout: None,
         }
     }
 
-    /// Gets the current position of the cursor (-1 idx)
+    /// Creates a lexer over a byte buffer (without copying it) if it's valid utf-8
     #[inline]
-    pub fn rposition(&self) -> crate::cursor::Position {
-        let mut clone = self.cursor.clone();
-        clone.revance();
-        clone.position()
+    pub fn from_bytes(file_name: String, bytes: Vec<u8>) -> Result<Self, FromUtf8Error> {
+        Ok(Self::from_source(file_name, String::from_utf8(bytes)?))
+    }
+
+    /// Creates a lexer over everything a reader produces, read chunk by chunk into a single buffer
+    /// (the whole input is kept, as positions point back into it)
+    pub fn from_reader(file_name: String, mut reader: impl Read) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::from_bytes(file_name, bytes).map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))
+    }
+
+    /// Creates a lexer over input that isn't from a file (like REPL input or macro expansions),
+    /// named `<virtual 1>`, `<virtual 2>`, etc so its positions are still distinct
+    #[inline]
+    pub fn virtual_file(source: impl Into<Source>) -> Self {
+        let id = VIRTUAL_FILES.fetch_add(1, Ordering::Relaxed) + 1;
+        Self::from_source(format!("<virtual {id}>"), source)
+    }
+
+    /// Moves the lexer to a line and line index of the file
+    #[inline]
+    pub fn seek(&mut self, ln: usize, ln_idx: usize) {
+        self.seek_to(self.cursor.file.contents.offset(ln, ln_idx));
+    }
+
+    /// Moves the lexer to a byte offset of the file
+    #[inline]
+    pub fn seek_to(&mut self, offset: usize) {
+        let cursor = self.cursor.pos_end.at(offset);
+        self.cursor = MutCursor { pos_start: cursor, pos_end: cursor, ..self.cursor.spawn() };
+        self.current = cursor.get_char_in(&self.cursor.file);
+    }
+
+    /// Spawns from a lexer again, reusing this lexer instead of making a new one (for trying rules one after the other)
+    #[inline]
+    pub fn respawn(&mut self, parent: &Lext) {
+        self.cursor.pos_start = parent.cursor.pos_end;
+        self.cursor.pos_end = parent.cursor.pos_end;
+        self.cursor.current_char = parent.cursor.current_char;
+        self.current = parent.current;
+    }
+
+    /// Moves the end of the lexer to a cursor in its file, along with the character there
+    #[inline]
+    pub fn jump(&mut self, end: Cursor, current: Option<char>) {
+        self.cursor.update(end);
+        self.cursor.current_char = current;
+        self.current = current;
+    }
+
+    /// Gets the character `n` characters ahead without moving (`peek(0)` is the current character)
+    #[inline]
+    pub fn peek(&self, n: usize) -> Option<char> {
+        let mut cursor = self.cursor.pos_end;
+        let mut current = self.current;
+        for _ in 0..n { current = cursor.advance_in(&self.cursor.file) }
+        current
+    }
+
+    /// Checks if the text from the current character on starts with `text` (without moving)
+    pub fn starts_with(&self, text: &str) -> bool {
+        let mut chars = text.chars();
+        let Some(first) = chars.next() else { return true };
+        if self.current != Some(first) { return false }
+
+        let mut cursor = self.cursor.pos_end;
+        chars.all(|x| cursor.advance_in(&self.cursor.file) == Some(x))
+    }
+
+    /// Moves past `text` if the lexer is on it
+    #[inline]
+    pub fn eat(&mut self, text: &str) -> bool {
+        if !self.starts_with(text) { return false }
+        text.chars().for_each(|_| self.advance());
+        true
+    }
+
+    /// Moves past (and collects) every character that matches `predicate`
+    #[inline]
+    pub fn eat_while(&mut self, mut predicate: impl FnMut(char) -> bool) -> String {
+        let mut eaten = String::new();
+        while let Some(x) = self.current.filter(|x| predicate(*x)) {
+            eaten.push(x);
+            self.advance();
+        } eaten
+    }
+
+    /// Resumes lexing with the state a lexer had after an earlier token (from `tokenize_from`)
+    #[inline]
+    pub fn resume(mut self, state: LexState) -> Self {
+        self.modes = state.modes;
+        self.layout = state.layout;
+        self
+    }
+
+    /// Gets the state of the lexer along with its layout (used by the `lexer!` macro)
+    #[doc(hidden)]
+    #[inline]
+    pub fn state(&self, layout: Option<&Layout>) -> LexState {
+        LexState { modes: self.modes.clone(), layout: layout.cloned() }
+    }
+
+    /// Takes the layout to resume from, or a new one (used by the `lexer!` macro)
+    #[doc(hidden)]
+    #[inline]
+    pub fn take_layout(&mut self) -> Layout {
+        self.layout.take().unwrap_or_default()
+    }
+
+    /// Gets the current lexer mode
+    #[inline]
+    pub fn mode(&self) -> Option<&'static str> {
+        self.modes.as_ref().map(|x| x.name)
+    }
+
+    /// Enters a lexer mode
+    #[inline]
+    pub fn push_mode(&mut self, mode: &'static str) {
+        self.modes = Some(Arc::new(Mode { name: mode, prev: self.modes.take() }));
+    }
+
+    /// Goes back to the previous lexer mode, returning the mode it left (or `None` outside of any mode)
+    #[inline]
+    pub fn pop_mode(&mut self) -> Option<&'static str> {
+        let mode = self.modes.take()?;
+        self.modes = mode.prev.clone();
+        Some(mode.name)
+    }
+
+    /// Gets a zero-width position at the end of the file
+    #[inline]
+    pub fn eof(&self) -> Position {
+        Position::empty(&self.cursor.pos_end.at(self.cursor.file.contents.source().as_str().len()))
+    }
+
+    /// Gets the position of what's been lexed since the start of the cursor (up to the current character)
+    #[inline]
+    pub fn rposition(&self) -> Position {
+        self.cursor.position()
     }
 }
 
@@ -48,12 +245,16 @@ impl Flext for Lext {
         Self {
             cursor: self.cursor.spawn(),
             current: self.current,
+            modes: self.modes.clone(), // only bumps a reference count
+            layout: None,
         }
     }
 
-    /// Gets the current position of the cursor
+    /// Gets the position from the start of the cursor through the current character
     #[inline]
     fn position(&self) -> crate::cursor::Position {
-        self.cursor.position()
+        let mut end = self.cursor.pos_end;
+        end.advance_in(&self.cursor.file);
+        Position(self.cursor.pos_start, end)
     }
 }
\ No newline at end of file
@@ -160,7 +160,6 @@
 //! 
 //! #[derive(Debug)]
 //! pub enum ProgramFile {
-//!     Single(Node<Stmt>),
 //!     Program(Box<[Node<Stmt>]>),
 //! }
 //!
@@ -210,34 +209,19 @@
 //!             } (else Err(E009: parxt.current_token()))
 //!         } (else Err(E008: parxt.current_token()))
 //!     } else Err(E006: parxt.current_token());
-//! }
 //!
-//! flexar::parser! {
-//!     [[ProgramFile] parxt: Token]
-//!     single {
+//!     statement {
 //!         [stmt: Stmt::parse] => {
-//!             (Semi) => (Single(stmt));
+//!             (Semi) => [stmt];
 //!         } (else Err(E010: parxt.current_token()))
 //!     } else Err(E006: parxt.current_token());
 //! }
 //!
-//! impl ProgramFile {
-//!     pub fn parse(tokens: &[token_node::Token<Token>]) -> Option<Self> {
-//!         if tokens.len() == 0 { return None }
-//!
-//!         let mut parxt = Parxt::new(tokens);
-//!         let mut stmts = Vec::new();
-//!
-//!         while parxt.current().is_some() {
-//!             match Self::single(&mut parxt) {
-//!                 Ok(Node { node: Self::Single(x), .. }) => stmts.push(x),
-//!                 Err((_, x)) => x.throw(),
-//!                 _ => panic!("not possible"),
-//!             }
-//!         }
-//!
-//!         Some(Self::Program(stmts.into_boxed_slice()))
-//!     }
+//! flexar::parser! {
+//!     [[ProgramFile] parxt: Token]
+//!     parse_all {
+//!         [stmts: Stmt::statement]* => (Program(stmts.into_boxed_slice()));
+//!     } else Err(E006: parxt.current_token());
 //! }
 //!
 //! //////////////////////////
@@ -251,11 +235,11 @@
 //!
 //! impl ProgramFile {
 //!     pub fn visit(&self) {
-//!         if let Self::Program(stmts) = self {
-//!             let mut visit_ctx = VisitCtx(HashMap::new(), stmts[0].position.clone());
-//!             stmts.iter()
-//!                 .for_each(|x| {x.visit(&mut visit_ctx);});
-//!         }
+//!         let Self::Program(stmts) = self;
+//!         let Some(first) = stmts.first() else { return };
+//!         let mut visit_ctx = VisitCtx(HashMap::new(), first.position);
+//!         stmts.iter()
+//!             .for_each(|x| {x.visit(&mut visit_ctx);});
 //!     }
 //! }
 //!
@@ -318,12 +302,29 @@
 //!     }
 //! }
 //! 
+pub mod ast_macro;
 pub mod compile_error;
+pub mod cst;
 pub mod cursor;
+pub mod driver;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+pub mod highlight;
+pub mod incremental;
+pub mod layout;
 pub mod lext;
+#[cfg(feature = "lsp")]
+pub mod lsp;
 pub mod lexer_macro;
+pub mod lexers;
 pub mod parser_macro;
 pub mod parxt;
 pub mod flext;
+pub mod testing;
 pub mod token_node;
-pub mod prelude;
\ No newline at end of file
+pub mod tree;
+pub mod visit;
+pub mod prelude;
+#[cfg(feature = "serde")]
+mod serialize;
+pub mod source_map;
\ No newline at end of file
@@ -0,0 +1,171 @@
+//! A minimal language server (requires the `lsp` feature)
+//!
+//! Serves `textDocument/didOpen`, `textDocument/didChange` and `textDocument/didClose` for a `lexer!` token type and a
+//! top-level `parser!` function, and publishes the `CompileError`s of each document as diagnostics
+//! (including errors thrown by the lexer, which are caught instead of exiting)
+//! - Positions are in UTF-16 code units, the encoding every client supports
+use std::{collections::HashMap, fmt::{Debug, Display}, io::{self, BufRead, Write}};
+use serde_json::{json, Value};
+use crate::{compile_error::CompileError, cursor::{Cursor, Position}, incremental::{Document, ItemParser, ResumableTokenizer, TextEdit}, visit::Walk};
+
+/// A language server for a single language
+pub struct Server<TT: Display, N: Debug> {
+    pub documents: HashMap<String, Document<TT, N>>,
+    tokenize: ResumableTokenizer<TT>,
+    parse: ItemParser<TT, N>,
+    shutdown: bool,
+}
+
+impl<TT: Display + PartialEq, N: Walk> Server<TT, N> {
+    #[inline]
+    pub fn new(tokenize: ResumableTokenizer<TT>, parse: ItemParser<TT, N>) -> Self {
+        Self { documents: HashMap::new(), tokenize, parse, shutdown: false }
+    }
+
+    /// Serves requests over stdio until the client asks the server to exit
+    #[inline]
+    pub fn serve_stdio(&mut self) -> io::Result<()> {
+        self.serve(&mut io::stdin().lock(), &mut io::stdout().lock())
+    }
+
+    /// Serves requests until the input ends or the client asks the server to exit
+    pub fn serve(&mut self, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<()> {
+        while let Some(message) = read_message(input)? {
+            if message["method"] == "exit" { break }
+            for x in self.handle(&message) {
+                write_message(output, &x)?;
+            }
+        } Ok(())
+    }
+
+    /// Handles a single message from the client and returns the messages to send back
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        match message["method"].as_str().unwrap_or_default() {
+            "initialize" => vec![response(message, json!({
+                "capabilities": {
+                    "textDocumentSync": 2, // incremental
+                    "positionEncoding": "utf-16",
+                },
+            }))],
+            "shutdown" => {
+                self.shutdown = true;
+                vec![response(message, Value::Null)]
+            },
+            "textDocument/didOpen" if !self.shutdown => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_string(), Document::new(uri.to_string(), text.to_string(), self.tokenize, self.parse));
+                vec![self.diagnostics(uri)]
+            },
+            "textDocument/didChange" if !self.shutdown => {
+                let Some(document) = self.documents.get_mut(uri) else { return Vec::new() };
+                for change in params["contentChanges"].as_array().into_iter().flatten() {
+                    let text = change["text"].as_str().unwrap_or_default();
+                    match change.get("range") {
+                        Some(range) => {
+                            let edit = TextEdit::new(from_lsp(&document.contents, &range["start"]), from_lsp(&document.contents, &range["end"]), text);
+                            let _ = document.edit(&edit); // edits that don't fit the document are ignored
+                        },
+                        None => *document = Document::new(uri.to_string(), text.to_string(), self.tokenize, self.parse),
+                    }
+                }
+                vec![self.diagnostics(uri)]
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                vec![notification("textDocument/publishDiagnostics", json!({ "uri": uri, "diagnostics": [] }))]
+            },
+            _ if message.get("id").is_some() => vec![json!({
+                "jsonrpc": "2.0",
+                "id": message["id"],
+                "error": { "code": -32601, "message": "method not found" },
+            })],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Generates a `textDocument/publishDiagnostics` notification for a document
+    pub fn diagnostics(&self, uri: &str) -> Value {
+        let diagnostics = self.documents.get(uri)
+            .map(|x| x.errors().map(diagnostic).collect::<Vec<_>>())
+            .unwrap_or_default();
+        notification("textDocument/publishDiagnostics", json!({ "uri": uri, "diagnostics": diagnostics }))
+    }
+}
+
+/// Converts a compile error into an LSP diagnostic
+pub fn diagnostic(error: &CompileError) -> Value {
+    json!({
+        "range": range(&error.position),
+        "severity": 1, // error
+        "code": error.id,
+        "source": "flexar",
+        "message": format!("{}: {}", error.error_type, error.msg),
+    })
+}
+
+/// Converts a position into an LSP range (zero based, with an exclusive end)
+#[inline]
+pub fn range(position: &Position) -> Value {
+    json!({ "start": to_lsp(&position.0), "end": to_lsp(&position.1) })
+}
+
+/// Converts a cursor into an LSP position (with the character in UTF-16 code units)
+fn to_lsp(cursor: &Cursor) -> Value {
+    let file = cursor.source_file();
+    let ln = cursor.location_in(&file).0;
+    let line_start = file.contents.offset(ln, 1);
+    let source = file.contents.source().as_str();
+    let character = source.get(line_start..cursor.offset().min(source.len())).map_or(0, |x| x.encode_utf16().count());
+    json!({ "line": ln - 1, "character": character })
+}
+
+/// Converts an LSP position (with the character in UTF-16 code units) into a `(ln, ln_idx)` pair of a document
+fn from_lsp(contents: &str, position: &Value) -> (usize, usize) {
+    let ln = position["line"].as_u64().unwrap_or(0) as usize;
+    let units = position["character"].as_u64().unwrap_or(0) as usize;
+    let line = contents.split('\n').nth(ln).unwrap_or_default();
+    let ln_idx = line.chars()
+        .scan(0, |seen, x| { *seen += x.len_utf16(); Some(*seen) })
+        .take_while(|seen| *seen <= units)
+        .count();
+    (ln + 1, ln_idx + 1)
+}
+
+#[inline]
+fn response(request: &Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+}
+
+#[inline]
+pub fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// Reads a `Content-Length` framed message (`None` when the input ends)
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 { return Ok(None) }
+        let line = line.trim_end();
+        if line.is_empty() { break }
+        if let Some(x) = line.strip_prefix("Content-Length:") {
+            length = x.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing `Content-Length` header"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(io::Error::from)
+}
+
+/// Writes a `Content-Length` framed message
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
@@ -1,5 +1,5 @@
 use std::{fs, time::Instant, collections::HashMap, fmt::Debug};
-use flexar::prelude::*;
+use flexar::{prelude::*, lexers, compile_error::CompileError};
 
 //////////////////////////
 // Errors
@@ -28,19 +28,22 @@ flexar::lexer! {
     [[Token] lext, current, 'cycle]
     else flexar::compiler_error!((E001, lext.position()) current).throw();
 
-    token_types {
-        LParen => "(";
-        RParen => ")";
-        Int(val: u32) => val;
-        Float(val: f32) => val;
-        Plus => "+";
-        Minus => "-";
-        Mul => "*";
-        Div => "/";
+    keywords(Ident) {
         Let => "let";
-        EQ => "=";
-        Semi => ";";
-        Ident(val: String) => val;
+    }
+
+    token_types {
+        [punctuation] LParen => "(";
+        [punctuation] RParen => ")";
+        [number] Int(val: u64) => val;
+        [number] Float(val: f64) => val;
+        [operator] Plus => "+";
+        [operator] Minus => "-";
+        [operator] Mul => "*";
+        [operator] Div => "/";
+        [operator] EQ => "=";
+        [punctuation] Semi => ";";
+        [identifier] Ident(val: String) => val;
     }
 
     Plus: +;
@@ -53,51 +56,17 @@ flexar::lexer! {
     [" \n\t"] >> ({ lext.advance(); lext = lext.spawn(); continue 'cycle; });
     
     / child {
-        advance: current;
-        ck (current, /) {
-            rsome current {
-                { if current == '\n' { lext = child; continue 'cycle } };
-            };
-        };
-        advance:();
+        if (lexers::line_comment(&mut child, "//")) { trivia: (); };
+        advance: ();
         done Div();
     };
-    
-    ["abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_"] child {
-        set ident { String::new() };
-        rsome (current, 'ident) {
-            set matched false;
-            ck (current, ["abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_-0123456789"]) {
-                mut matched true;
-                { ident.push(current) };
-            };
-            { if !matched { break 'ident } };
-        };
-        if (ident == "let") { done Let(); };
-        done Ident(ident);
-    };
 
-    ["0123456789"] child {
-        set number { String::new() };
-        set dot false;
-        rsome (current, 'number) {
-            set matched false;
-            ck (current, ["0123456789"]) {
-                mut matched true;
-                { number.push(current) };
-            };
-            ck (current, '.') {
-                if (dot) {
-                    done Float(number.parse().unwrap());
-                };
-                mut matched true;
-                mut dot true;
-                { number.push(current) };
-            };
-            {if !matched {break 'number}};
-        };
-        if (dot) { done Float(number.parse().unwrap()); };
-        done Int(number.parse().unwrap());
+    Ident ~ ([alpha, '_'] [alpha, digit, '_', '-']*);
+
+    [digit] child {
+        set number { lexers::number(&mut child).unwrap_or_else(|x| CompileError::from(x).throw()) };
+        if (number.is_float()) { done Float(number.as_f64()); };
+        done Int(number.as_u64());
     };
 }
 
@@ -105,38 +74,45 @@ flexar::lexer! {
 // Parser
 //////////////////////////
 
-#[derive(Debug)]
-pub enum Stmt {
-    Expr(Node<Expr>),
-    Let(String, Node<Expr>),
+flexar::ast! {
+    #[derive(Debug)]
+    pub enum Stmt {
+        Expr(Node<Expr>),
+        Let(String, Node<Expr>),
+    }
 }
 
-#[derive(Debug)]
-pub enum Expr {
-    Plus(Node<Factor>, Box<Node<Expr>>),
-    Minus(Node<Factor>, Box<Node<Expr>>),
-    Factor(Node<Factor>),
+flexar::ast! {
+    #[derive(Debug)]
+    pub enum Expr {
+        Plus(Node<Factor>, Box<Node<Expr>>),
+        Minus(Node<Factor>, Box<Node<Expr>>),
+        Factor(Node<Factor>),
+    }
 }
 
-#[derive(Debug)]
-pub enum Factor {
-    Mul(Node<Number>, Box<Node<Factor>>),
-    Div(Node<Number>, Box<Node<Factor>>),
-    Number(Node<Number>),
+flexar::ast! {
+    #[derive(Debug)]
+    pub enum Factor {
+        Mul(Node<Number>, Box<Node<Factor>>),
+        Div(Node<Number>, Box<Node<Factor>>),
+        Number(Node<Number>),
+    }
 }
 
-#[derive(Debug)]
-pub enum Number {
-    Get(String),
-    Neg(Box<Node<Number>>),
-    Expr(Box<Node<Expr>>),
-    Int(u32),
-    Float(f32),
+flexar::ast! {
+    #[derive(Debug)]
+    pub enum Number {
+        Get(String),
+        Neg(Box<Node<Number>>),
+        Expr(Box<Node<Expr>>),
+        Int(u64),
+        Float(f64),
+    }
 }
 
 #[derive(Debug)]
 pub enum ProgramFile {
-    Single(Node<Stmt>),
     Program(Box<[Node<Stmt>]>),
 }
 
@@ -186,34 +162,19 @@ flexar::parser! {
             } (else Err(E009: parxt.current_token()))
         } (else Err(E008: parxt.current_token()))
     } else Err(E006: parxt.current_token());
-}
 
-flexar::parser! {
-    [[ProgramFile] parxt: Token]
-    single {
+    statement {
         [stmt: Stmt::parse] => {
-            (Semi) => (Single(stmt));
+            (Semi) => [stmt];
         } (else Err(E010: parxt.current_token()))
     } else Err(E006: parxt.current_token());
 }
 
-impl ProgramFile {
-    pub fn parse(tokens: &[token_node::Token<Token>]) -> Option<Self> {
-        if tokens.is_empty() { return None }
-
-        let mut parxt = Parxt::new(tokens);
-        let mut stmts = Vec::new();
-
-        while parxt.current().is_some() {
-            match Self::single(&mut parxt) {
-                Ok(Node { node: Self::Single(x), .. }) => stmts.push(x),
-                Err((_, x)) => x.throw(),
-                _ => panic!("not possible"),
-            }
-        }
-
-        Some(Self::Program(stmts.into_boxed_slice()))
-    }
+flexar::parser! {
+    [[ProgramFile] parxt: Token]
+    parse_all {
+        [stmts: Stmt::statement]* => (Program(stmts.into_boxed_slice()));
+    } else Err(E006: parxt.current_token());
 }
 
 //////////////////////////
@@ -227,17 +188,17 @@ pub trait Visit {
 
 impl ProgramFile {
     pub fn visit(&self) {
-        if let Self::Program(stmts) = self {
-            let mut visit_ctx = VisitCtx(HashMap::new(), stmts[0].position.clone());
-            stmts.iter()
-                .for_each(|x| {x.visit(&mut visit_ctx);});
-        }
+        let Self::Program(stmts) = self;
+        let Some(first) = stmts.first() else { return };
+        let mut visit_ctx = VisitCtx(HashMap::new(), first.position);
+        stmts.iter()
+            .for_each(|x| {x.visit(&mut visit_ctx);});
     }
 }
 
 impl<N: Visit + Debug> Visit for Node<N> {
     fn visit(&self, visit_ctx: &mut VisitCtx) -> f32 {
-        visit_ctx.1 = self.position.clone();
+        visit_ctx.1 = self.position;
         self.node.visit(visit_ctx)
     }
 }
@@ -248,9 +209,9 @@ impl Visit for Number {
         match self {
             N::Int(x) => *x as f32,
             N::Neg(x) => -x.visit(visit_ctx),
-            N::Float(x) => *x,
+            N::Float(x) => *x as f32,
             N::Get(x) => *visit_ctx.0.get(x).unwrap_or_else(||
-                compiler_error!((RT001, visit_ctx.1.clone()) x).throw()
+                compiler_error!((RT001, visit_ctx.1) x).throw()
             ),
             N::Expr(x) => x.visit(visit_ctx)
         }
@@ -303,19 +264,26 @@ fn main() {
 
     // Lexer
         let first_time = Instant::now();
-    let tokens = Token::tokenize(Lext::new("example.fx".into(), &contents));
+    let lext = Lext::new("example.fx".into(), &contents);
+    let eof = lext.eof();
+    let tokens = Token::tokenize(lext);
         print_time("Tokenising completed in", first_time);
     println!("{:?}", tokens.iter().map(|x| &x.token_type).collect::<Box<[&Token]>>());
 
     // Parser
         let time = Instant::now();
-    let node = ProgramFile::parse(&tokens);
+    let node = ProgramFile::parse_all(&tokens, eof).unwrap_or_else(|errors| errors[0].throw());
         print_time("Parsing completed in", time);
-        let node = match node {
-            Some(x) => x,
-            None => return,
-        };
-    println!("{:#?}", node);
+    match std::env::args().nth(1).as_deref() { // `--tree` or `--sexpr` to show a compact tree
+        Some(flag @ ("--tree" | "--sexpr")) => {
+            let ProgramFile::Program(stmts) = &*node;
+            stmts.iter().for_each(|x| match flag {
+                "--tree" => println!("{}", x.tree().positions(true)),
+                _ => println!("{}", x.tree().sexpr()),
+            });
+        },
+        _ => println!("{:#?}", node),
+    }
         
     // Interpreter
         let time = Instant::now();
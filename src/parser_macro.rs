@@ -1,131 +1,239 @@
 /// Creates a parsing function for a node
+/// - Failures return a `ParseError`, and when several branches fail the one that got furthest through the tokens wins
+/// - A `parse_all { [item: Item::parse] => ... } else Err(...);` declaration (before the other functions) generates `parse_all(&[Token<_>], eof: Position) -> Result<Node<_>, Vec<CompileError>>` (with the end of the file from `Lext::eof`), which fails with the `else` error if any tokens are left over
+/// - With `[items: Item::parse]*` it parses top-level items until the tokens run out (stopping at the first error, or skipping tokens until an item parses again with `parse_all recover`)
+/// - Either can be followed by an end token (`[items: Item::parse]*, (Eof) => ...`) that's consumed once parsing is done
+/// - With the `fuzz` feature, the functions can also describe their grammar to generate programs from (see `fuzz::Grammar::parser`)
 #[macro_export]
 macro_rules! parser {
+    ([[$node:ty] $parxt:ident: $token:ident] parse_all $($recover:ident)? {[$out:ident: $func:expr]* $(, ($end:ident))? => $body:tt$end_body:tt} else Err($err:ident $(:$($rest:expr),+)?); $($tail:tt)*) => {
+        impl $node {
+            /// Parses every token into top-level items (see `parser!`)
+            pub fn parse_all(tokens: &[$crate::token_node::Token<$token>], eof: $crate::cursor::Position) -> Result<$crate::token_node::Node<$node>, Vec<$crate::compile_error::CompileError>> {
+                let mut $parxt = $crate::parxt::Parxt::new(tokens).with_eof(eof);
+                let leftover = |$parxt: &$crate::parxt::Parxt<'_, $token>| $crate::compiler_error!(($err, $parxt.position()) $($($rest),*)?);
+                let start = $parxt.position();
+                #[allow(unused_variables)]
+                let ($out, mut errors) = $crate::parxt::parse_items(&mut $parxt, $crate::parser!(@recover $($recover)?), |x| $crate::parser!(@is-end x $token $($end)?), $func, |x, _| Err(leftover(x)));
+                $crate::parser!(@all $token $parxt start errors leftover [$($end)?] $body$end_body)
+            }
+        }
+        $crate::parser!([[$node] $parxt: $token] $($tail)*);
+    };
+
+    ([[$node:ty] $parxt:ident: $token:ident] parse_all {[$out:ident: $func:expr] $(, ($end:ident))? => $body:tt$end_body:tt} else Err($err:ident $(:$($rest:expr),+)?); $($tail:tt)*) => {
+        impl $node {
+            /// Parses every token into a single node (see `parser!`)
+            pub fn parse_all(tokens: &[$crate::token_node::Token<$token>], eof: $crate::cursor::Position) -> Result<$crate::token_node::Node<$node>, Vec<$crate::compile_error::CompileError>> {
+                let mut $parxt = $crate::parxt::Parxt::new(tokens).with_eof(eof);
+                let leftover = |$parxt: &$crate::parxt::Parxt<'_, $token>| $crate::compiler_error!(($err, $parxt.position()) $($($rest),*)?);
+                let start = $parxt.position();
+                let mut errors = Vec::new();
+                #[allow(clippy::redundant_closure_call)]
+                let $out = match ($func)(&mut $parxt) {
+                    Ok(x) => x,
+                    Err(x) => return Err(vec![x.error]),
+                };
+                $crate::parser!(@all $token $parxt start errors leftover [$($end)?] $body$end_body)
+            }
+        }
+        $crate::parser!([[$node] $parxt: $token] $($tail)*);
+    };
+
     ([[$node:ty] $parxt:ident: $token:ident] $($func:ident {$($($pats:tt),* => $body:tt$end:tt)*} else $else:ident$else_body:tt;)*) => {
         #[allow(clippy::diverging_sub_expression)]
         impl $node {
-            $(pub fn $func($parxt: &mut $crate::parxt::Parxt<'_, $token>) -> Result<$crate::token_node::Node<$node>, (u8, $crate::compile_error::CompileError)> {
-                let mut last_error: Option<(u8, $crate::compile_error::CompileError)> = None;
-                let start_pos = $parxt.position();
-                let mut child = $parxt.spawn();
-
-                $($crate::parser!(@req $token start_pos $parxt child last_error 0, $($pats),* => $body$end);)*
-                
-                if let Some((i, x)) = last_error { if i > 0 { return Err((i, x)); } }
-                Err($crate::parser!(@else start_pos $parxt $else$else_body 0))
+            $(pub fn $func($parxt: &mut $crate::parxt::Parxt<'_, $token>) -> Result<$crate::token_node::Node<$node>, $crate::parxt::ParseError> {
+                $crate::parser_describe! {
+                    if let Some(describe) = $parxt.describe {
+                        return Err(describe.rule(concat!(stringify!($node), "::", stringify!($func)), $parxt.position(), || {
+                            let options: Vec<Option<$crate::fuzz::Gen>> = vec![
+                                $(Some($crate::parser!(@gen describe $parxt $token, $($pats),* => $body$end)),)*
+                                $crate::parser!(@gen-else describe $parxt $token $else$else_body),
+                            ];
+                            $crate::fuzz::Gen::Choice(options.into_iter().flatten().collect())
+                        }));
+                    }
+                }
+                let checkpoint = $parxt.checkpoint();
+
+                let result: Result<$crate::token_node::Node<$node>, $crate::parxt::ParseError> = 'rule: {
+                    let mut furthest = $crate::parxt::Furthest::default();
+                    let (start_pos, start_idx) = ($parxt.position(), $parxt.idx());
+                    let mut child = $parxt.spawn();
+
+                    $({
+                        let trace_mark = $parxt.trace_mark();
+                        $crate::parser!(@req 'rule $token start_pos $parxt child furthest, $($pats),* => $body$end);
+                        $parxt.trace_rewind(trace_mark);
+                    })*
+                    
+                    if furthest.past(start_idx) { break 'rule Err(furthest.take().expect("it got past the start")) }
+                    Err($crate::parser!(@else 'rule start_pos $parxt furthest $else$else_body))
+                };
+
+                $parxt.trace_node(stringify!($node), checkpoint, result.is_ok());
+                result
             })*
         }
     };
 
+    // Parse all
+    (@all $token:ident $parxt:ident $start:ident $errors:ident $leftover:ident [$($end:ident)?] $body:tt;) => {{
+        $(if let Some($token::$end) = $parxt.current() { $crate::flext::Flext::advance(&mut $parxt) })?
+        if $errors.is_empty() && $parxt.current().is_some() { $errors.push($leftover(&$parxt)) }
+        if !$errors.is_empty() { return Err($errors) }
+        let position = match $parxt.idx().checked_sub(1) {
+            Some(last) => $start.combine(&$parxt.tokens[last].position),
+            None => $start,
+        };
+        #[allow(unused_parens)]
+        Ok($crate::parser!(@all-body position $body))
+    }};
+
+    (@all-body $position:ident ($node:ident$($data:tt)?)) => { $crate::token_node::Node::new($position, Self::$node$($data)?) };
+    (@all-body $position:ident ($data:tt)) => { $crate::token_node::Node::new($position, Self$data) };
+    (@all-body $position:ident [$node:expr]) => { $node };
+
+    (@is-end $current:ident $token:ident) => {{ let _ = $current; false }};
+    (@is-end $current:ident $token:ident $end:ident) => { matches!($current, $token::$end) };
+
+    (@recover) => { false };
+    (@recover recover) => { true };
+    (@recover $invalid:ident) => { compile_error!(concat!("[parser] invalid `parse_all` option `", stringify!($invalid), "`, expected `recover`")) };
+
     // Requirements
-    (@req $token:ident $start_pos:ident $parxt:ident $child:ident $last_error:ident $depth:expr, [$out:ident: $func:expr], $($tail:tt),* => $body:tt$end:tt) => {
+    (@req $label:lifetime $token:ident $start_pos:ident $parxt:ident $child:ident $furthest:ident, [$out:ident: $func:expr], $($tail:tt),* => $body:tt$end:tt) => {
         #[allow(unused_parens)]
         match $func(&mut $child) {
             Ok($out) => {
-                $crate::parser!(@req $token $start_pos $parxt $child $last_error $depth + 1, $($tail),* => $body$end);
+                $crate::parser!(@req $label $token $start_pos $parxt $child $furthest, $($tail),* => $body$end);
             }
-            Err((i, x)) => {
-                let i = i + $depth;
-                match $last_error {
-                    None => $last_error = Some((i, x)),
-                    Some((ii, _)) => if i > ii {
-                        $last_error = Some((i, x));
-                    },
-                }
-            },
+            Err(error) => $furthest.fail(error),
         };
     };
 
-    (@req $token:ident $start_pos:ident $parxt:ident $child:ident $last_error:ident $depth:expr, [$out:ident: $func:expr] => $body:tt$end:tt) => {
+    (@req $label:lifetime $token:ident $start_pos:ident $parxt:ident $child:ident $furthest:ident, [$out:ident: $func:expr] => $body:tt$end:tt) => {
         #[allow(unused_parens)]
         match $func(&mut $child) {
             Ok($out) => {
-                $crate::parser!(@body $token $start_pos $parxt $child $last_error $body$end | $depth + 1);
+                $crate::parser!(@body $label $token $start_pos $parxt $child $furthest $body$end);
             }
-            Err((i, x)) => {
-                let i = i + $depth;
-                match $last_error {
-                    None => $last_error = Some((i, x)),
-                    Some((ii, _)) => if i > ii {
-                        $last_error = Some((i, x));
-                    },
-                }
-            },
+            Err(error) => $furthest.fail(error),
         };
     };
 
-    (@req $token:ident $start_pos:ident $parxt:ident $child:ident $last_error:ident $depth:expr, ($head:ident$($neck:tt)?), $($tail:tt),* => $body:tt$end:tt) => {
+    (@req $label:lifetime $token:ident $start_pos:ident $parxt:ident $child:ident $furthest:ident, ($head:ident$($neck:tt)?), $($tail:tt),* => $body:tt$end:tt) => {
         #[allow(unused_parens)]
         if let Some($token::$head$($neck)?) = $child.current() {
             $child.advance();
-            $crate::parser!(@req $token $start_pos $parxt $child $last_error $depth + 1, $($tail),* => $body$end);
-        }
+            $crate::parser!(@req $label $token $start_pos $parxt $child $furthest, $($tail),* => $body$end);
+        } else { $furthest.expect($child.idx(), stringify!($head)) }
     };
 
-    (@req $token:ident $start_pos:ident $parxt:ident $child:ident $last_error:ident $depth:expr, ($head:ident$($neck:tt)?) => $body:tt$end:tt) => {
+    (@req $label:lifetime $token:ident $start_pos:ident $parxt:ident $child:ident $furthest:ident, ($head:ident$($neck:tt)?) => $body:tt$end:tt) => {
         #[allow(unused_parens)]
         if let Some($token::$head$($neck)?) = $child.current() {
             $child.advance();
-            $crate::parser!(@body $token $start_pos $parxt $child $last_error $body$end | $depth + 1);
-        }
+            $crate::parser!(@body $label $token $start_pos $parxt $child $furthest $body$end);
+        } else { $furthest.expect($child.idx(), stringify!($head)) }
     };
 
     // Body
-    (@body $token:ident $start_pos:ident $parxt:ident $child:ident $last_error:ident {$($($pats:tt),* => $body:tt$end:tt)*} $((else $else:ident$else_body:tt))? $(;)? | $depth:expr) => {
-        let mut last_error: Option<(u8, $crate::compile_error::CompileError)> = None;
+    (@body $label:lifetime $token:ident $start_pos:ident $parxt:ident $child:ident $furthest:ident {$($($pats:tt),* => $body:tt$end:tt)*} $((else $else:ident$else_body:tt))? $(;)?) => {
         let mut child = $child.spawn();
         
-        $($crate::parser!(@req $token $start_pos $parxt child $last_error $depth, $($pats),* => $body$end);)*
-        if let Some((i, x)) = last_error {
-            $last_error = Some((i, x));
-        }
-        $(#[allow(unreachable_code)]
-        match $last_error {
-            Some((i, _)) if i > $depth => *$parxt = $child.clone(), // if things break remove this,
-            _ => {
-                *$parxt = $child.clone(); // if things break remove this
-                $last_error = Some($crate::parser!(@else $start_pos $parxt $else$else_body $depth));
-            },
+        $({
+            let trace_mark = $parxt.trace_mark();
+            $crate::parser!(@req $label $token $start_pos $parxt child $furthest, $($pats),* => $body$end);
+            $parxt.trace_rewind(trace_mark);
+        })*
+        $(#[allow(unreachable_code)] {
+            *$parxt = $child.clone(); // if things break remove this
+            if !$furthest.past($parxt.idx()) {
+                let error = $crate::parser!(@else $label $start_pos $parxt $furthest $else$else_body);
+                $furthest.fail(error);
+            }
         })?
     };
 
-    (@body $token:ident $start_pos:ident $parxt:ident $child:ident $last_error:ident ($node:ident$($data:tt)?); | $depth:expr) => {
+    (@body $label:lifetime $token:ident $start_pos:ident $parxt:ident $child:ident $furthest:ident ($node:ident$($data:tt)?);) => {
         let pos = $parxt.position();
         *$parxt = $child.clone();
-        return Ok($crate::token_node::Node::new($start_pos.combine(&pos), Self::$node$($data)?));
+        break $label Ok($crate::token_node::Node::new($start_pos.combine(&pos), Self::$node$($data)?));
     };
 
-    (@body $token:ident $start_pos:ident $parxt:ident $child:ident $last_error:ident ($data:tt); | $depth:expr) => {
+    (@body $label:lifetime $token:ident $start_pos:ident $parxt:ident $child:ident $furthest:ident ($data:tt);) => {
         let pos = $parxt.position();
         *$parxt = $child.clone();
-        return Ok($crate::token_node::Node::new($start_pos.combine(&pos), Self$data));
+        break $label Ok($crate::token_node::Node::new($start_pos.combine(&pos), Self$data));
     };
 
-    (@body $token:ident $start_pos:ident $parxt:ident $child:ident $last_error:ident [$node:expr]; | $depth:expr) => {
+    (@body $label:lifetime $token:ident $start_pos:ident $parxt:ident $child:ident $furthest:ident [$node:expr];) => {
         *$parxt = $child.clone();
-        return Ok($node);
+        break $label Ok($node);
     };
 
     // Else
-    (@else $start_pos:ident $parxt:ident Err($err:ident $(:$($rest:expr),+)?) $depth:expr) => {
-        ($depth, $crate::compiler_error!(($err, $parxt.position()) $($($rest),*)?))
+    (@else $label:lifetime $start_pos:ident $parxt:ident $furthest:ident Err($err:ident $(:$($rest:expr),+)?)) => {
+        $furthest.with_expected($crate::parxt::ParseError::new($parxt.idx(), $crate::compiler_error!(($err, $parxt.position()) $($($rest),*)?)))
     };
 
-    (@else $start_pos:ident $parxt:ident Ok($else:expr) $depth:expr) => {
-        return Ok($crate::token_node::Node::new($start_pos, $else));
+    (@else $label:lifetime $start_pos:ident $parxt:ident $furthest:ident Ok($else:expr)) => {
+        break $label Ok($crate::token_node::Node::new($start_pos, $else));
     };
 
-    (@else $start_pos:ident $parxt:ident Raw($raw:expr) $depth:expr) => {
-        return $raw;
+    (@else $label:lifetime $start_pos:ident $parxt:ident $furthest:ident Raw($raw:expr)) => {
+        break $label $raw;
     };
 
-    (@else $start_pos:ident $parxt:ident Other($variant:ident $else:expr) $depth:expr) => {
+    (@else $label:lifetime $start_pos:ident $parxt:ident $furthest:ident Other($variant:ident $else:expr)) => {
         match $else {
-            Ok(x) => return Ok($crate::token_node::Node::new($start_pos.combine(&x.position), Self::$variant(x))),
-            Err((i, x)) => ((i + $depth, x)),
+            Ok(x) => break $label Ok($crate::token_node::Node::new($start_pos.combine(&x.position), Self::$variant(x))),
+            Err(error) => $furthest.with_expected(error),
         }
     };
 
+    // Grammar
+    (@gen $describe:ident $parxt:ident $token:ident, $($pats:tt),* => $body:tt$end:tt) => {
+        $crate::fuzz::Gen::Seq(vec![$($crate::parser!(@gen-pat $describe $parxt $token $pats),)* $crate::parser!(@gen-body $describe $parxt $token $body$end)])
+    };
+
+    (@gen-pat $describe:ident $parxt:ident $token:ident [$out:ident: $($path:ident)::+]) => {
+        $describe.call($crate::parser!(@path-name $($path)::+), |$parxt: &mut $crate::parxt::Parxt<'_, $token>| { let _ = $($path)::+($parxt); })
+    };
+
+    (@path-name $head:ident $(:: $rest:ident)*) => {
+        concat!(stringify!($head) $(, "::", stringify!($rest))*)
+    };
+
+    (@gen-pat $describe:ident $parxt:ident $token:ident [$out:ident: $func:expr]) => {
+        $crate::fuzz::Gen::rule(stringify!($func))
+    };
+
+    (@gen-pat $describe:ident $parxt:ident $token:ident ($head:ident$($neck:tt)?)) => {
+        $crate::fuzz::Gen::token(stringify!($head))
+    };
+
+    (@gen-body $describe:ident $parxt:ident $token:ident {$($($pats:tt),* => $body:tt$end:tt)*} $((else $else:ident$else_body:tt))? $(;)?) => {{
+        let options: Vec<Option<$crate::fuzz::Gen>> = vec![
+            $(Some($crate::parser!(@gen $describe $parxt $token, $($pats),* => $body$end)),)*
+            $($crate::parser!(@gen-else $describe $parxt $token $else$else_body),)?
+        ];
+        $crate::fuzz::Gen::Choice(options.into_iter().flatten().collect())
+    }};
+
+    (@gen-body $describe:ident $parxt:ident $token:ident $body:tt;) => {
+        $crate::fuzz::Gen::Seq(Vec::new())
+    };
+
+    (@gen-else $describe:ident $parxt:ident $token:ident Ok($else:expr)) => { Some($crate::fuzz::Gen::Seq(Vec::new())) };
+    (@gen-else $describe:ident $parxt:ident $token:ident Other($variant:ident $($path:ident)::+ ($arg:ident))) => {
+        Some($describe.call($crate::parser!(@path-name $($path)::+), |$arg: &mut $crate::parxt::Parxt<'_, $token>| { let _ = $($path)::+($arg); }))
+    };
+    (@gen-else $describe:ident $parxt:ident $token:ident Other($variant:ident $else:expr)) => { Some($crate::fuzz::Gen::rule(stringify!($else))) };
+    (@gen-else $describe:ident $parxt:ident $token:ident $else:ident$else_body:tt) => { None };
+
     // Outputs
     (@output $variant:ident $rest:tt) => {
         Self::$variant$crate::parser!(@output $rest)
@@ -134,4 +242,20 @@ macro_rules! parser {
     (@output $rest:tt) => {
         Self$rest
     };
-}
\ No newline at end of file
+}
+
+/// Keeps the grammar descriptions of `parser!` functions with the `fuzz` feature (and drops them without it)
+#[cfg(feature = "fuzz")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! parser_describe {
+    ($($code:tt)*) => { $($code)* };
+}
+
+/// Keeps the grammar descriptions of `parser!` functions with the `fuzz` feature (and drops them without it)
+#[cfg(not(feature = "fuzz"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! parser_describe {
+    ($($code:tt)*) => {};
+}
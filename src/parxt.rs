@@ -1,13 +1,21 @@
-use std::{rc::Rc, fmt::Display};
+use std::{fmt::{Debug, Display}, ops::Range};
 
-use crate::{flext::Flext, token_node::Token, cursor::Position};
+use crate::{compile_error::CompileError, flext::Flext, token_node::{Node, Token}, cursor::Position, cst::{Trace, TraceSpan}, source_map};
+#[cfg(feature = "fuzz")]
+use crate::fuzz::Describe;
 
 /// Parser context for parsing
 #[derive(Debug, Clone, Copy)]
 pub struct Parxt<'a, TT: Display> {
     pub tokens: &'a [Token<TT>],
-    pub idx: u16,
-    pub done: bool,
+    idx: usize,
+    pub trace: Option<&'a Trace>,
+    /// The end of the file the tokens are from (used as the position of empty input)
+    pub eof: Option<Position>,
+    /// Makes `parser!` functions describe their grammar instead of parsing (see `fuzz::Grammar::parser`)
+    #[cfg(feature = "fuzz")]
+    #[doc(hidden)]
+    pub describe: Option<&'a Describe>,
 }
 
 impl<'a, TT: Display> Parxt<'a, TT> {
@@ -16,13 +24,82 @@ impl<'a, TT: Display> Parxt<'a, TT> {
         Self {
             tokens,
             idx: 0,
-            done: tokens.is_empty(),
+            trace: None,
+            eof: None,
+            #[cfg(feature = "fuzz")]
+            describe: None,
         }
     }
 
+    /// Sets the end of the file the tokens are from (`Lext::eof`), so empty input still has a position
+    #[inline]
+    pub fn with_eof(mut self, eof: Position) -> Self {
+        self.eof = Some(eof);
+        self
+    }
+
+    /// Makes `parser!` functions describe their grammar into `describe` instead of parsing (see `fuzz::Grammar::parser`)
+    #[cfg(feature = "fuzz")]
+    #[doc(hidden)]
+    #[inline]
+    pub fn describing(mut self, describe: &'a Describe) -> Self {
+        self.describe = Some(describe);
+        self
+    }
+
+    /// Records the spans of the parsed nodes into a trace (for building a concrete syntax tree)
+    #[inline]
+    pub fn traced(mut self, trace: &'a Trace) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    /// Saves the current token index (and how much has been traced) to rewind back to
+    #[inline]
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint { idx: self.idx, trace: self.trace_mark() }
+    }
+
+    /// A checkpoint at another token index (for starting part way through the tokens)
+    #[inline]
+    pub fn checkpoint_at(&self, idx: usize) -> Checkpoint {
+        Checkpoint { idx: idx.min(self.tokens.len()), trace: self.trace_mark() }
+    }
+
+    /// Goes back to a checkpoint, forgetting any nodes traced since
+    #[inline]
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.idx = checkpoint.idx;
+        self.trace_rewind(checkpoint.trace);
+    }
+
+    #[inline]
+    pub fn trace_mark(&self) -> usize {
+        self.trace.map_or(0, |x| x.mark())
+    }
+
+    #[inline]
+    pub fn trace_rewind(&self, mark: usize) {
+        if let Some(trace) = self.trace { trace.rewind(mark) }
+    }
+
+    /// Records a parsed node if it succeeded, otherwise forgets everything it recorded
+    #[inline]
+    pub fn trace_node(&self, kind: &'static str, start: Checkpoint, ok: bool) {
+        let Some(trace) = self.trace else { return };
+        if ok { trace.push(TraceSpan { kind, start: start.idx, end: self.idx }) }
+        else { trace.rewind(start.trace) }
+    }
+
+    /// The index of the current token (moved with `Flext::advance`, `Flext::revance` and checkpoints)
+    #[inline]
+    pub fn idx(&self) -> usize {
+        self.idx
+    }
+
     #[inline]
     pub fn current_token(&self) -> Option<&'a Token<TT>> {
-        self.tokens.get(self.idx as usize)
+        self.tokens.get(self.idx)
     }
 
     #[inline]
@@ -30,15 +107,28 @@ impl<'a, TT: Display> Parxt<'a, TT> {
         self.current_token().map(|x| &x.token_type)
     }
 
+    /// The position once the tokens run out (right after the last token, or the end of the file without any tokens)
     #[inline]
     fn get_last_pos(&self) -> Position {
-        if self.tokens.is_empty() { panic!("file's empty, gonna add code to handle that later") }
-        let mut position = self.tokens[self.tokens.len() -1].position.clone();
-        let mut end = (*position.1).clone();
-        end.ln_idx += 1;
-        let end = Rc::new(end);
-        (position.0, position.1) = (end.clone(), end);
-        position
+        let Some(last) = self.tokens.last() else {
+            return self.eof.unwrap_or_else(|| Position::eof(source_map::empty_file()));
+        };
+        Position::empty(&last.position.1)
+    }
+}
+
+/// A spot in the tokens that a `Parxt` can rewind back to (from `Parxt::checkpoint`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    idx: usize,
+    trace: usize,
+}
+
+impl Checkpoint {
+    /// The index of the token the checkpoint is at
+    #[inline]
+    pub fn idx(&self) -> usize {
+        self.idx
     }
 }
 
@@ -60,13 +150,144 @@ impl<'a, TT: Display> Flext for Parxt<'a, TT> {
     /// Spawns a child flext
     #[inline]
     fn spawn(&self) -> Self {
-        Self { tokens: self.tokens, idx: self.idx, done: self.done, }
+        Self {
+            tokens: self.tokens,
+            idx: self.idx,
+            trace: self.trace,
+            eof: self.eof,
+            #[cfg(feature = "fuzz")]
+            describe: self.describe,
+        }
     }
 
     /// Gets the current position of the cursor
     #[inline]
     fn position(&self) -> Position {
-        self.current_token().map(|x| x.position.clone())
+        self.current_token().map(|x| x.position)
             .unwrap_or_else(|| self.get_last_pos())
     }
-}
\ No newline at end of file
+}
+/// An error from a `parser!` function
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    /// The index of the furthest token reached before failing (the furthest failure wins)
+    pub furthest: usize,
+    /// The token types and nodes that were expected at the furthest token
+    pub expected: Vec<&'static str>,
+    pub error: CompileError,
+}
+
+impl ParseError {
+    #[inline]
+    pub fn new(furthest: usize, error: CompileError) -> Self {
+        Self { furthest, expected: Vec::new(), error }
+    }
+}
+
+impl From<ParseError> for CompileError {
+    #[inline]
+    fn from(error: ParseError) -> Self {
+        error.error
+    }
+}
+
+/// Tracks the furthest failure of the branches of a `parser!` function (used by the `parser!` macro)
+#[doc(hidden)]
+#[derive(Debug, Default)]
+pub struct Furthest {
+    error: Option<ParseError>,
+    expected: Option<(usize, Vec<&'static str>)>,
+}
+
+impl Furthest {
+    /// Records a failed sub-node (merging what was expected if it got as far as the furthest one)
+    pub fn fail(&mut self, error: ParseError) {
+        match &mut self.error {
+            Some(x) if x.furthest > error.furthest => (),
+            Some(x) if x.furthest == error.furthest => error.expected.into_iter().for_each(|y| add(&mut x.expected, y)),
+            _ => self.error = Some(error),
+        }
+    }
+
+    /// Records a token that didn't match
+    pub fn expect(&mut self, idx: usize, what: &'static str) {
+        match &mut self.expected {
+            Some((x, _)) if *x > idx => (),
+            Some((x, expected)) if *x == idx => add(expected, what),
+            _ => self.expected = Some((idx, vec![what])),
+        }
+    }
+
+    /// Checks if a failure got past a token index
+    #[inline]
+    pub fn past(&self, idx: usize) -> bool {
+        self.error.as_ref().is_some_and(|x| x.furthest > idx)
+    }
+
+    /// Takes the furthest failure (with the tokens expected as far as it got)
+    pub fn take(&mut self) -> Option<ParseError> {
+        let mut error = self.error.take()?;
+        if let Some((_, expected)) = self.expected.take().filter(|x| x.0 == error.furthest) {
+            expected.into_iter().for_each(|x| add(&mut error.expected, x));
+        } Some(error)
+    }
+
+    /// Adds everything that was expected as far as an error got to it (clearing what was recorded)
+    pub fn with_expected(&mut self, mut error: ParseError) -> ParseError {
+        if let Some(x) = self.error.take().filter(|x| x.furthest == error.furthest) {
+            x.expected.into_iter().for_each(|x| add(&mut error.expected, x));
+        }
+        if let Some((_, expected)) = self.expected.take().filter(|x| x.0 == error.furthest) {
+            expected.into_iter().for_each(|x| add(&mut error.expected, x));
+        } error
+    }
+}
+
+#[inline]
+fn add(expected: &mut Vec<&'static str>, what: &'static str) {
+    if !expected.contains(&what) { expected.push(what) }
+}
+
+/// Parses a single top-level item, skipping a token to recover when it fails or doesn't consume anything
+/// - Returns the tokens the item covers along with the item (`stuck` decides what an item that consumed nothing becomes)
+pub fn parse_item<'a, TT: Display, N: Debug>(
+    parxt: &mut Parxt<'a, TT>,
+    parse: impl FnOnce(&mut Parxt<'a, TT>) -> Result<Node<N>, ParseError>,
+    stuck: impl FnOnce(&Parxt<'a, TT>, Node<N>) -> Result<Node<N>, CompileError>,
+) -> (Range<usize>, Result<Node<N>, CompileError>) {
+    let checkpoint = parxt.checkpoint();
+    let node = match parse(parxt) {
+        Ok(x) if parxt.idx > checkpoint.idx() => return (checkpoint.idx()..parxt.idx, Ok(x)),
+        Ok(x) => stuck(parxt, x), // an item that doesn't consume anything would never end
+        Err(x) => Err(x.error),
+    };
+    parxt.rewind(checkpoint);
+    parxt.advance();
+    (checkpoint.idx()..parxt.idx, node)
+}
+
+/// Parses top-level items until the tokens (or an end token) run out
+/// - Without `recover` it stops at the first error, otherwise it skips tokens until an item parses again (with one error
+///   for every run of skipped tokens)
+/// - `stuck` decides what an item that consumed nothing becomes (like with `parse_item`)
+pub fn parse_items<'a, TT: Display, N: Debug>(
+    parxt: &mut Parxt<'a, TT>,
+    recover: bool,
+    is_end: impl Fn(&TT) -> bool,
+    parse: impl Fn(&mut Parxt<'a, TT>) -> Result<Node<N>, ParseError>,
+    stuck: impl Fn(&Parxt<'a, TT>, Node<N>) -> Result<Node<N>, CompileError>,
+) -> (Vec<Node<N>>, Vec<CompileError>) {
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    let mut recovering = false;
+
+    while let Some(current) = parxt.current() {
+        if is_end(current) { break }
+        match parse_item(parxt, &parse, &stuck).1 {
+            Ok(x) => { items.push(x); recovering = false; continue },
+            Err(x) => if !recovering { errors.push(x) },
+        }
+        if !recover { break }
+        recovering = true;
+    } (items, errors)
+}
@@ -1,2 +1,2 @@
 //! Prelude (Common imports with `flexar`)
-pub use crate::{flext::Flext, token_node::{self, TokenToString, Token, Node}, parxt::Parxt, lext::Lext, cursor::Position, compiler_error};
\ No newline at end of file
+pub use crate::{flext::Flext, token_node::{self, TokenToString, Token, Node}, parxt::{Parxt, ParseError}, lext::Lext, cursor::Position, compiler_error};
\ No newline at end of file
@@ -0,0 +1,98 @@
+//! Serde support for positions (behind the `serde` feature)
+//! - Cursors and positions are serialised as their file name and a hash of its contents plus the line, column and byte
+//!   offset of each end (the end of a position is exclusive)
+//! - The file has to already be in the global source map to deserialise them (with the same name and contents), and the
+//!   line and column have to match the offset
+use std::sync::Arc;
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use crate::{cursor::{Cursor, Position}, source_map::{self, FileId, SourceFile}};
+
+/// A spot in a file
+#[derive(Serialize, Deserialize)]
+struct Spot {
+    ln: usize,
+    col: usize,
+    offset: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerialCursor {
+    file: String,
+    hash: String,
+    ln: usize,
+    col: usize,
+    offset: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerialPosition {
+    file: String,
+    hash: String,
+    start: Spot,
+    end: Spot,
+}
+
+impl From<&Cursor> for Spot {
+    #[inline]
+    fn from(cursor: &Cursor) -> Self {
+        let (ln, col) = cursor.location();
+        Self { ln, col, offset: cursor.offset() }
+    }
+}
+
+impl Spot {
+    /// Moves a cursor to the spot, checking that its line and column match its offset
+    fn cursor<E: Error>(&self, id: FileId, file: &SourceFile) -> Result<Cursor, E> {
+        if !file.contents.source().as_str().is_char_boundary(self.offset) {
+            return Err(E::custom(format_args!("offset {} isn't in file `{}`", self.offset, file.name)));
+        }
+        if file.contents.location(self.offset) != (self.ln, self.col) {
+            return Err(E::custom(format_args!("{}:{} isn't at offset {} of file `{}`", self.ln, self.col, self.offset, file.name)));
+        }
+        Ok(Cursor::from_file(id).at(self.offset))
+    }
+}
+
+/// The hash of a file's contents as it's serialised
+#[inline]
+fn hash(file: &SourceFile) -> String {
+    format!("{:016x}", file.hash())
+}
+
+/// Finds a file in the global source map by its name and the hash of its contents
+fn find<E: Error>(name: &str, hash: &str) -> Result<(FileId, Arc<SourceFile>), E> {
+    let parsed = u64::from_str_radix(hash, 16).map_err(|_| E::custom(format_args!("`{hash}` isn't a file hash")))?;
+    source_map::with(|x| x.named(name).find(|(_, x)| x.hash() == parsed).map(|(id, x)| (id, x.clone())))
+        .ok_or_else(|| E::custom(format_args!("file `{name}` (with hash {hash}) isn't in the source map")))
+}
+
+impl Serialize for Cursor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let file = self.source_file();
+        let Spot { ln, col, offset } = self.into();
+        SerialCursor { file: file.name.to_string(), hash: hash(&file), ln, col, offset }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cursor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let cursor = SerialCursor::deserialize(deserializer)?;
+        let (id, file) = find(&cursor.file, &cursor.hash)?;
+        Spot { ln: cursor.ln, col: cursor.col, offset: cursor.offset }.cursor(id, &file)
+    }
+}
+
+impl Serialize for Position {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let file = self.0.source_file();
+        SerialPosition { file: file.name.to_string(), hash: hash(&file), start: (&self.0).into(), end: (&self.1).into() }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Position {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let position = SerialPosition::deserialize(deserializer)?;
+        let (id, file) = find(&position.file, &position.hash)?;
+        Ok(Position(position.start.cursor(id, &file)?, position.end.cursor(id, &file)?))
+    }
+}
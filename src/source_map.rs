@@ -0,0 +1,173 @@
+//! A registry of every source file, so positions only need to store a `FileId`
+//! - Files stay in the global source map until they're removed with `remove_file` (incremental documents and driver
+//!   output remove theirs when they're dropped), and positions into a removed file are rendered at the start of an empty
+//!   `<removed name>` file instead
+//! - Lexers hold onto their file instead of looking it up for every character, so the lock is only taken to add, update,
+//!   remove or render files
+use std::{collections::BTreeMap, sync::{Arc, OnceLock, PoisonError, RwLock}};
+use crate::cursor::{FileContents, Source};
+
+/// The global source map that lexers register their files with
+static SOURCE_MAP: RwLock<SourceMap> = RwLock::new(SourceMap::new());
+
+/// Refers to a file in a source map (only a source map can make one)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(u32);
+
+/// A file in a source map
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: Arc<str>,
+    pub contents: FileContents,
+    hash: OnceLock<u64>,
+    removed: bool,
+}
+
+impl SourceFile {
+    #[inline]
+    fn new(name: Arc<str>, source: Source) -> Self {
+        Self { name, contents: FileContents::from_source(source), hash: OnceLock::new(), removed: false }
+    }
+
+    /// An empty stand-in for a removed file, so positions into it can still be rendered
+    #[inline]
+    fn removed(name: &str) -> Self {
+        Self { removed: true, ..Self::new(format!("<removed {name}>").into(), Source::Static("")) }
+    }
+
+    /// Checks if the file is a stand-in for a removed one
+    #[inline]
+    pub fn is_removed(&self) -> bool {
+        self.removed
+    }
+
+    /// A hash of the file's contents (FNV-1a, so it's the same in every build and process)
+    pub fn hash(&self) -> u64 {
+        *self.hash.get_or_init(|| self.contents.source().as_str().bytes()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, x| (hash ^ x as u64).wrapping_mul(0x0100_0000_01b3)))
+    }
+}
+
+/// A registry of source files
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<Arc<SourceFile>>, // removed files are swapped for a stand-in so ids aren't reused
+    names: BTreeMap<Arc<str>, Vec<FileId>>, // the files with each name (that haven't been removed)
+}
+
+impl SourceMap {
+    #[inline]
+    pub const fn new() -> Self {
+        Self { files: Vec::new(), names: BTreeMap::new() }
+    }
+
+    /// Adds a file (without copying its source)
+    pub fn add(&mut self, name: impl Into<Arc<str>>, source: impl Into<Source>) -> FileId {
+        let name = name.into();
+        let id = FileId(self.files.len().try_into().expect("too many files in the source map"));
+        self.files.push(Arc::new(SourceFile::new(name.clone(), source.into())));
+        self.names.entry(name).or_default().push(id);
+        id
+    }
+
+    /// Gets a file with the same name and contents, or adds it if there isn't one
+    pub fn intern(&mut self, name: impl Into<Arc<str>>, source: impl Into<Source>) -> FileId {
+        let (name, source) = (name.into(), source.into());
+        let found = self.named(&name).find(|(_, x)| x.contents.source().as_str() == source.as_str()).map(|(id, _)| id);
+        found.unwrap_or_else(|| self.add(name, source))
+    }
+
+    /// Removes a file, freeing its contents once nothing else holds onto it
+    pub fn remove(&mut self, id: FileId) -> Option<Arc<SourceFile>> {
+        let slot = self.files.get_mut(id.0 as usize).filter(|x| !x.removed)?;
+        let file = std::mem::replace(slot, Arc::new(SourceFile::removed(&slot.name)));
+        if let Some(ids) = self.names.get_mut(&file.name) {
+            ids.retain(|x| *x != id);
+            if ids.is_empty() { self.names.remove(&file.name); }
+        } Some(file)
+    }
+
+    /// Replaces the contents of a file, keeping its id and name (positions into it will point into the new contents)
+    /// (does nothing if the file was removed)
+    pub fn update(&mut self, id: FileId, source: impl Into<Source>) {
+        let Some(file) = self.files.get_mut(id.0 as usize).filter(|x| !x.removed) else { return };
+        *file = Arc::new(SourceFile::new(file.name.clone(), source.into()));
+    }
+
+    #[inline]
+    pub fn get(&self, id: FileId) -> Option<&Arc<SourceFile>> {
+        self.files.get(id.0 as usize).filter(|x| !x.removed)
+    }
+
+    /// Finds the first file with a name
+    #[inline]
+    pub fn find(&self, name: &str) -> Option<FileId> {
+        self.named(name).next().map(|(id, _)| id)
+    }
+
+    /// Every file with a name (without going through the other files)
+    #[inline]
+    pub fn named(&self, name: &str) -> impl Iterator<Item = (FileId, &Arc<SourceFile>)> {
+        self.names.get(name).into_iter().flatten().map(|id| (*id, &self.files[id.0 as usize]))
+    }
+
+    #[inline]
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &Arc<SourceFile>)> {
+        self.files.iter().enumerate().filter(|(_, x)| !x.removed).map(|(i, x)| (FileId(i as u32), x))
+    }
+
+    /// The amount of files (that haven't been removed)
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.files().count()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.files().next().is_none()
+    }
+}
+
+/// Runs a function with the global source map
+#[inline]
+pub fn with<R>(f: impl FnOnce(&SourceMap) -> R) -> R {
+    f(&SOURCE_MAP.read().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// Adds a file to the global source map
+#[inline]
+pub fn add_file(name: impl Into<Arc<str>>, source: impl Into<Source>) -> FileId {
+    SOURCE_MAP.write().unwrap_or_else(PoisonError::into_inner).add(name, source)
+}
+
+/// Gets a file with the same name and contents from the global source map, or adds it if there isn't one
+#[inline]
+pub fn intern_file(name: impl Into<Arc<str>>, source: impl Into<Source>) -> FileId {
+    SOURCE_MAP.write().unwrap_or_else(PoisonError::into_inner).intern(name, source)
+}
+
+/// Replaces the contents of a file in the global source map
+#[inline]
+pub fn update_file(id: FileId, source: impl Into<Source>) {
+    SOURCE_MAP.write().unwrap_or_else(PoisonError::into_inner).update(id, source)
+}
+
+/// Removes a file from the global source map
+#[inline]
+pub fn remove_file(id: FileId) -> Option<Arc<SourceFile>> {
+    SOURCE_MAP.write().unwrap_or_else(PoisonError::into_inner).remove(id)
+}
+
+/// Gets a file from the global source map
+/// - A removed file (or one from another source map) gives an empty stand-in instead
+#[inline]
+pub fn file(id: FileId) -> Arc<SourceFile> {
+    with(|x| x.files.get(id.0 as usize).cloned()).unwrap_or_else(|| Arc::new(SourceFile::removed("file")))
+}
+
+/// An empty file in the global source map (for positions that don't belong to any file, like the end of an empty token stream)
+#[inline]
+pub fn empty_file() -> FileId {
+    static EMPTY: OnceLock<FileId> = OnceLock::new();
+    *EMPTY.get_or_init(|| add_file("<empty>", ""))
+}
@@ -0,0 +1,165 @@
+//! Snapshot tests for lexers and parsers
+//! - Every fixture file in a directory is lexed (and parsed) and rendered into a snapshot of its tokens, AST and diagnostics,
+//!   which is compared against the `<fixture>.snap` file next to it
+//! - Set the `FLEXAR_BLESS` environment variable to write the snapshots instead (for new fixtures or grammar changes)
+//! - Errors thrown while lexing end up in the snapshot's diagnostics (see `CompileError::catch`)
+//! ```ignore
+//! #[test]
+//! fn test_snapshots() {
+//!     Snapshots::new("tests/snapshots", Token::tokenize_with)
+//!         .parser(Stmt::parse)
+//!         .run();
+//! }
+//! ```
+use std::{fmt::{Debug, Display, Write}, fs, path::{Path, PathBuf}};
+use crate::{compile_error::CompileError, cursor::Position, incremental::{ItemParser, Tokenizer}, lext::Lext, parxt::{parse_items, Parxt}, source_map::{self, FileId}, token_node::Token, visit::Walk};
+
+/// The environment variable that makes snapshot tests write their snapshots instead of comparing against them
+pub const BLESS_VAR: &str = "FLEXAR_BLESS";
+
+/// Renders the AST and diagnostics of a token stream into a snapshot
+type Render<TT> = Box<dyn Fn(&[Token<TT>], Position, &mut String)>;
+
+/// Snapshot tests over a directory of fixture files
+pub struct Snapshots<TT: Display> {
+    dir: PathBuf,
+    tokenize: Tokenizer<TT>,
+    parse: Option<Render<TT>>,
+    bless: bool,
+}
+
+impl<TT: Display + Debug + 'static> Snapshots<TT> {
+    #[inline]
+    pub fn new(dir: impl Into<PathBuf>, tokenize: Tokenizer<TT>) -> Self {
+        let bless = std::env::var_os(BLESS_VAR).is_some_and(|x| !x.is_empty() && x != "0");
+        Self { dir: dir.into(), tokenize, parse: None, bless }
+    }
+
+    /// Overrides whether to write the snapshots instead of comparing against them (from `FLEXAR_BLESS` by default)
+    #[inline]
+    pub fn bless(mut self, bless: bool) -> Self {
+        self.bless = bless;
+        self
+    }
+
+    /// Also parses the tokens into top-level items (skipping tokens until an item parses again after every error)
+    pub fn parser<N: Walk>(mut self, parse: ItemParser<TT, N>) -> Self {
+        self.parse = Some(Box::new(move |tokens, eof, out| {
+            let mut parxt = Parxt::new(tokens).with_eof(eof);
+            let (items, errors) = parse_items(&mut parxt, true, |_| false, parse, |_, x| Ok(x));
+
+            out.push_str("=== ast ===\n");
+            items.iter().for_each(|x| { let _ = writeln!(out, "{}", x.tree()); });
+
+            if errors.is_empty() { return }
+            out.push_str("=== diagnostics ===");
+            errors.iter().for_each(|x| diagnostic(x, out));
+        }));
+        self
+    }
+
+    /// Renders the snapshot of a fixture
+    pub fn snapshot(&self, name: &str, contents: &str) -> String {
+        let file = source_map::add_file(name, contents.to_string());
+        let out = self.render(file);
+        source_map::remove_file(file);
+        out
+    }
+
+    /// Renders the snapshot of a file in the global source map
+    fn render(&self, file: FileId) -> String {
+        let mut out = String::from("=== tokens ===\n");
+        let tokenize = self.tokenize;
+        let tokens = match CompileError::catch(|| tokenize(Lext::from_file(file), &mut |_| false)) {
+            Ok(tokens) => tokens,
+            Err(error) => {
+                out.push_str("=== diagnostics ===");
+                diagnostic(&error, &mut out);
+                return out;
+            },
+        };
+
+        for token in tokens.iter() {
+            let (start, end) = (token.position.start(), token.position.end());
+            let _ = writeln!(out, "{}:{}-{}:{} {:?}", start.0, start.1, end.0, end.1, token.token_type);
+        }
+        if let Some(parse) = &self.parse { parse(&tokens, Position::eof(file), &mut out) }
+        out
+    }
+
+    /// Compares (or blesses) the snapshot of every fixture, returning the differences
+    pub fn check(&self) -> Result<(), String> {
+        let mut fixtures = fs::read_dir(&self.dir)
+            .unwrap_or_else(|x| panic!("couldn't read the fixtures in `{}`: {x}", self.dir.display()))
+            .map(|x| x.expect("couldn't read a fixture").path())
+            .filter(|x| x.is_file() && x.extension().is_none_or(|x| x != "snap"))
+            .collect::<Vec<_>>();
+        fixtures.sort();
+
+        let mut failures = String::new();
+        for fixture in fixtures {
+            let name = fixture.file_name().expect("fixtures are files").to_string_lossy().into_owned();
+            let contents = fs::read_to_string(&fixture).unwrap_or_else(|x| panic!("couldn't read `{}`: {x}", fixture.display()));
+            let actual = self.snapshot(&name, &contents);
+            let path = snap_path(&fixture);
+
+            if self.bless {
+                fs::write(&path, &actual).unwrap_or_else(|x| panic!("couldn't write `{}`: {x}", path.display()));
+                continue;
+            }
+            match fs::read_to_string(&path) {
+                Ok(expected) if expected == actual => (),
+                Ok(expected) => { let _ = write!(failures, "\nsnapshot `{}` doesn't match:\n{}", path.display(), diff(&expected, &actual)); },
+                Err(_) => { let _ = writeln!(failures, "\nsnapshot `{}` is missing", path.display()); },
+            }
+        }
+
+        match failures.is_empty() {
+            true => Ok(()),
+            false => Err(format!("{failures}\n(set `{BLESS_VAR}=1` to update the snapshots)")),
+        }
+    }
+
+    /// Compares (or blesses) the snapshot of every fixture, panicking with the differences
+    #[inline]
+    pub fn run(&self) {
+        if let Err(failures) = self.check() { panic!("{failures}") }
+    }
+}
+
+/// The snapshot file of a fixture
+#[inline]
+fn snap_path(fixture: &Path) -> PathBuf {
+    let mut path = fixture.as_os_str().to_owned();
+    path.push(".snap");
+    path.into()
+}
+
+/// Renders a compile error without its colours (after a blank line)
+fn diagnostic(error: &CompileError, out: &mut String) {
+    out.push('\n');
+    let mut chars = error.to_string().chars().collect::<Vec<_>>().into_iter();
+    while let Some(x) = chars.next() {
+        if x == '\x1b' { chars.by_ref().find(|x| *x == 'm'); }
+        else { out.push(x) }
+    }
+    out.push('\n');
+}
+
+/// A line diff of two snapshots (`-` for expected lines and `+` for actual ones)
+fn diff(expected: &str, actual: &str) -> String {
+    let (old, new) = (expected.lines().collect::<Vec<_>>(), actual.lines().collect::<Vec<_>>());
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let (mut i, mut j, mut out) = (0, 0, String::new());
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] { let _ = writeln!(out, "  {}", old[i]); i += 1; j += 1 }
+        else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) { let _ = writeln!(out, "- {}", old[i]); i += 1 }
+        else { let _ = writeln!(out, "+ {}", new[j]); j += 1 }
+    } out
+}
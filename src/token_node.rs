@@ -3,9 +3,62 @@ use crate::cursor::Position;
 
 /// A token generated by the lexer
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Token<TT: Display> {
     pub position: Position,
     pub token_type: TT,
+    pub trivia: Trivia,
+}
+
+impl<TT: Display> Token<TT> {
+    #[inline]
+    pub fn new(position: Position, token_type: TT) -> Self {
+        Self {
+            position,
+            token_type,
+            trivia: Trivia::default(),
+        }
+    }
+}
+
+/// A piece of source text the lexer marked as trivia (whitespace, comments, etc)
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TriviaPiece {
+    pub position: Position,
+    pub text: Box<str>,
+}
+
+/// The trivia surrounding a token
+/// - `trailing` is everything after the token up to the first piece containing a newline
+/// - `leading` is everything else before the token
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trivia {
+    pub leading: Box<[TriviaPiece]>,
+    pub trailing: Box<[TriviaPiece]>,
+}
+
+impl Trivia {
+    /// Attaches pending trivia pieces to the tokens around them (used by the `lexer!` macro)
+    pub fn attach<TT: Display>(tokens: &mut [Token<TT>], pending: &mut Vec<TriviaPiece>, next: Option<&mut Token<TT>>) {
+        // trivia before the first token or at the end of the file can only go one way
+        let split = match (tokens.is_empty(), &next) {
+            (true, _) => 0,
+            (false, None) => pending.len(),
+            (false, Some(_)) => pending.iter().position(|x| x.text.contains('\n')).unwrap_or(pending.len()),
+        };
+        let leading = pending.split_off(split);
+
+        if let Some(last) = tokens.last_mut() { last.trivia.trailing = std::mem::take(pending).into_boxed_slice() }
+        if let Some(next) = next { next.trivia.leading = leading.into_boxed_slice() }
+    }
+
+    /// Gets the full text of the trivia pieces
+    #[inline]
+    pub fn text(pieces: &[TriviaPiece]) -> String {
+        pieces.iter().map(|x| x.text.as_ref()).collect()
+    }
 }
 
 /// A trait required to display a token nicely
@@ -14,8 +67,9 @@ pub trait TokenToString {
 }
 
 impl<TT: Display> TokenToString for Option<&Token<TT>> {
+    /// The token type, or `end of file` without a token
     fn to_string(&self) -> String {
-        self.map_or(" ".into(), |x| x.token_type.to_string())
+        self.map_or("end of file".into(), |x| x.token_type.to_string())
     }
 }
 
@@ -40,6 +94,7 @@ impl<TT: Display> DerefMut for Token<TT> {
 
 /// A node generated by the parser
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node<N: Debug> {
     pub position: Position,
     pub node: N,
@@ -0,0 +1,102 @@
+//! Compact renderings of `Node` trees (for tests and debugging)
+//! ```text
+//! Plus
+//! ├─ Int 1
+//! └─ Neg
+//!    └─ Int 2
+//! ```
+use std::fmt::{self, Display};
+use crate::{cursor::Position, token_node::Node, visit::{Visitor, Walk}};
+
+/// Renders a tree of nodes as an indented tree (with box-drawing characters) or as an S-expression
+#[derive(Debug, Clone, Copy)]
+pub struct TreeDisplay<'a, N: Walk> {
+    node: &'a Node<N>,
+    positions: bool,
+    sexpr: bool,
+}
+
+impl<N: Walk> Node<N> {
+    /// Renders the node and its children as a tree
+    #[inline]
+    pub fn tree(&self) -> TreeDisplay<'_, N> {
+        TreeDisplay { node: self, positions: false, sexpr: false }
+    }
+}
+
+impl<N: Walk> TreeDisplay<'_, N> {
+    /// Adds the position of every node (as `@ ln:col-ln:col`)
+    #[inline]
+    pub fn positions(mut self, positions: bool) -> Self {
+        self.positions = positions;
+        self
+    }
+
+    /// Renders an S-expression (`(Plus (Int 1) (Int 2))`) instead of an indented tree
+    #[inline]
+    pub fn sexpr(mut self) -> Self {
+        self.sexpr = true;
+        self
+    }
+
+    fn label(&self, (label, position): &(String, Position)) -> String {
+        match self.positions {
+            true => {
+                let (start, end) = (position.start(), position.end());
+                format!("{label} @ {}:{}-{}:{}", start.0, start.1, end.0, end.1)
+            },
+            false => label.clone(),
+        }
+    }
+}
+
+/// Flattens a tree into its nodes and their depths
+#[derive(Default)]
+struct Flatten {
+    nodes: Vec<(usize, (String, Position))>,
+    depth: usize,
+}
+
+impl Visitor for Flatten {
+    fn enter<N: Walk>(&mut self, node: &N, position: Position) -> bool {
+        self.nodes.push((self.depth, (node.label(), position)));
+        self.depth += 1;
+        true
+    }
+
+    fn leave<N: Walk>(&mut self, _: &N, _: Position) {
+        self.depth -= 1;
+    }
+}
+
+impl<N: Walk> Display for TreeDisplay<'_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut flatten = Flatten::default();
+        self.node.visit_with(&mut flatten);
+        let nodes = flatten.nodes;
+
+        if self.sexpr {
+            for (i, (depth, node)) in nodes.iter().enumerate() {
+                if i > 0 { write!(f, " ")? }
+                write!(f, "({}", self.label(node))?;
+                let next = nodes.get(i + 1).map_or(0, |x| x.0);
+                if next <= *depth { write!(f, "{}", ")".repeat(depth - next + 1))? }
+            } return Ok(());
+        }
+
+        // whether each depth still has siblings to come (so it needs a `│`)
+        let mut open = Vec::new();
+        for (i, (depth, node)) in nodes.iter().enumerate() {
+            let last = nodes[i + 1..].iter().find(|x| x.0 <= *depth).is_none_or(|x| x.0 < *depth);
+            if i > 0 { writeln!(f)? }
+            if *depth > 0 {
+                for open in &open[1..*depth] { write!(f, "{}", if *open { "│  " } else { "   " })? }
+                write!(f, "{}", if last { "└─ " } else { "├─ " })?;
+            }
+            write!(f, "{}", self.label(node))?;
+
+            open.truncate(*depth);
+            open.push(!last);
+        } Ok(())
+    }
+}
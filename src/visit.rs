@@ -0,0 +1,201 @@
+//! Generic visitors and folds over trees of `Node`s
+//! - AST types get their `Walk` implementation by being defined inside `flexar::ast!`
+//! - Visitors are generic over the node type, so use `downcast_ref` (or `downcast_mut` / `downcast`) to handle a specific one
+use std::{any::Any, fmt::Debug};
+use crate::{cursor::Position, token_node::Node};
+
+/// An AST type whose child nodes can be walked (implemented by `flexar::ast!`)
+pub trait Walk: Debug + Sized + 'static {
+    /// Visits every child node
+    fn walk<V: Visitor>(&self, visitor: &mut V);
+    /// Visits every child node mutably
+    fn walk_mut<V: VisitorMut>(&mut self, visitor: &mut V);
+    /// Folds every child node
+    fn fold_children<F: Fold>(self, folder: &mut F) -> Self;
+
+    /// A short description of the node without its children (like `Int 3`)
+    #[inline]
+    fn label(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// Visits the nodes of a tree (along with their positions)
+pub trait Visitor {
+    /// Called on every node before its children, which are skipped if it returns `false`
+    #[inline]
+    fn enter<N: Walk>(&mut self, node: &N, position: Position) -> bool {
+        let _ = (node, position);
+        true
+    }
+
+    /// Called on every node after its children
+    #[inline]
+    fn leave<N: Walk>(&mut self, node: &N, position: Position) {
+        let _ = (node, position);
+    }
+}
+
+/// Visits the nodes of a tree mutably (along with their positions)
+pub trait VisitorMut {
+    /// Called on every node before its children, which are skipped if it returns `false`
+    #[inline]
+    fn enter<N: Walk>(&mut self, node: &mut N, position: Position) -> bool {
+        let _ = (node, position);
+        true
+    }
+
+    /// Called on every node after its children
+    #[inline]
+    fn leave<N: Walk>(&mut self, node: &mut N, position: Position) {
+        let _ = (node, position);
+    }
+}
+
+/// Rewrites the nodes of a tree
+pub trait Fold: Sized {
+    /// Rewrites a node (by default only its children are folded)
+    #[inline]
+    fn fold<N: Walk>(&mut self, node: Node<N>) -> Node<N> {
+        node.fold_children(self)
+    }
+}
+
+impl<N: Walk> Node<N> {
+    /// Visits the node and then its children
+    pub fn visit_with<V: Visitor>(&self, visitor: &mut V) {
+        if visitor.enter(&self.node, self.position) { self.node.walk(visitor) }
+        visitor.leave(&self.node, self.position);
+    }
+
+    /// Visits the node and then its children mutably
+    pub fn visit_mut_with<V: VisitorMut>(&mut self, visitor: &mut V) {
+        if visitor.enter(&mut self.node, self.position) { self.node.walk_mut(visitor) }
+        visitor.leave(&mut self.node, self.position);
+    }
+
+    /// Rewrites the node with a fold
+    #[inline]
+    pub fn fold_with<F: Fold>(self, folder: &mut F) -> Self {
+        folder.fold(self)
+    }
+
+    /// Folds the children of the node (keeping its position)
+    #[inline]
+    pub fn fold_children<F: Fold>(self, folder: &mut F) -> Self {
+        Node::new(self.position, self.node.fold_children(folder))
+    }
+}
+
+/// A field of an AST type that can contain nodes
+pub trait Child {
+    fn visit_child<V: Visitor>(&self, visitor: &mut V);
+    fn visit_child_mut<V: VisitorMut>(&mut self, visitor: &mut V);
+    fn fold_child<F: Fold>(self, folder: &mut F) -> Self;
+}
+
+impl<N: Walk> Child for Node<N> {
+    #[inline]
+    fn visit_child<V: Visitor>(&self, visitor: &mut V) {
+        self.visit_with(visitor)
+    }
+
+    #[inline]
+    fn visit_child_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
+        self.visit_mut_with(visitor)
+    }
+
+    #[inline]
+    fn fold_child<F: Fold>(self, folder: &mut F) -> Self {
+        self.fold_with(folder)
+    }
+}
+
+impl<T: Child> Child for Box<T> {
+    #[inline]
+    fn visit_child<V: Visitor>(&self, visitor: &mut V) {
+        (**self).visit_child(visitor)
+    }
+
+    #[inline]
+    fn visit_child_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
+        (**self).visit_child_mut(visitor)
+    }
+
+    #[inline]
+    fn fold_child<F: Fold>(self, folder: &mut F) -> Self {
+        Box::new((*self).fold_child(folder))
+    }
+}
+
+impl<T: Child> Child for Option<T> {
+    #[inline]
+    fn visit_child<V: Visitor>(&self, visitor: &mut V) {
+        if let Some(x) = self { x.visit_child(visitor) }
+    }
+
+    #[inline]
+    fn visit_child_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
+        if let Some(x) = self { x.visit_child_mut(visitor) }
+    }
+
+    #[inline]
+    fn fold_child<F: Fold>(self, folder: &mut F) -> Self {
+        self.map(|x| x.fold_child(folder))
+    }
+}
+
+impl<T: Child> Child for Vec<T> {
+    #[inline]
+    fn visit_child<V: Visitor>(&self, visitor: &mut V) {
+        self.iter().for_each(|x| x.visit_child(visitor))
+    }
+
+    #[inline]
+    fn visit_child_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
+        self.iter_mut().for_each(|x| x.visit_child_mut(visitor))
+    }
+
+    #[inline]
+    fn fold_child<F: Fold>(self, folder: &mut F) -> Self {
+        self.into_iter().map(|x| x.fold_child(folder)).collect()
+    }
+}
+
+impl<T: Child> Child for Box<[T]> {
+    #[inline]
+    fn visit_child<V: Visitor>(&self, visitor: &mut V) {
+        self.iter().for_each(|x| x.visit_child(visitor))
+    }
+
+    #[inline]
+    fn visit_child_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
+        self.iter_mut().for_each(|x| x.visit_child_mut(visitor))
+    }
+
+    #[inline]
+    fn fold_child<F: Fold>(self, folder: &mut F) -> Self {
+        self.into_vec().fold_child(folder).into_boxed_slice()
+    }
+}
+
+/// Gets a node as a specific AST type
+#[inline]
+pub fn downcast_ref<T: 'static>(node: &impl Any) -> Option<&T> {
+    (node as &dyn Any).downcast_ref()
+}
+
+/// Gets a node mutably as a specific AST type
+#[inline]
+pub fn downcast_mut<T: 'static>(node: &mut impl Any) -> Option<&mut T> {
+    (node as &mut dyn Any).downcast_mut()
+}
+
+/// Converts a node into a specific AST type (giving it back if it's a different type)
+pub fn downcast<N: 'static, T: 'static>(node: N) -> Result<T, N> {
+    let mut node = Some(node);
+    match (&mut node as &mut dyn Any).downcast_mut::<Option<T>>() {
+        Some(x) => Ok(x.take().expect("just created")),
+        None => Err(node.expect("just created")),
+    }
+}
@@ -0,0 +1,79 @@
+//! The language the integration tests share: `;`-terminated sums of numbers
+#![allow(dead_code)]
+use flexar::{prelude::*, compile_error::CompileError, lexers};
+
+flexar::compiler_error! {
+    [[Define] CompileErrors]
+    (E001) "invalid character": "`", "` is an invalid character";
+    (E002) "expected number": "expected number, found `", "`.";
+    (E003) "expected `;`": "expected `;`, found `", "`.";
+    (E004) "unexpected token": "unexpected token `", "`.";
+}
+
+flexar::lexer! {
+    [[TokenType] lext, current]
+    else flexar::compiler_error!((E001, lext.position()) current).throw();
+
+    token_types {
+        Plus => "+";
+        Semi => ";";
+        Bang => "!";
+        Int(val: u64) => val;
+        Str(val: String) => val;
+    }
+
+    Plus: +;
+    Semi: ;;
+    [" \n\t"] >> trivia;
+    / child {
+        if (lexers::line_comment(&mut child, "//")) { trivia: (); };
+        throw E001(child.position(), '/');
+    };
+    [digit] child {
+        set number { lexers::number(&mut child).unwrap_or_else(|x| CompileError::from(x).throw()) };
+        done Int(number.as_u64());
+    };
+    '"' child {
+        set string { lexers::string(&mut child, '"').unwrap_or_else(|x| CompileError::from(x).throw()) };
+        done Str(string);
+    };
+    // `!!` panics so the fuzzer has a bug to find
+    '!' child {
+        if (child.peek(1) == Some('!')) { { panic!("double bang") }; };
+        advance: ();
+        done Bang();
+    };
+}
+
+flexar::ast! {
+    #[derive(Debug)]
+    pub enum Expr {
+        Plus(u64, Box<Node<Expr>>),
+        Int(u64),
+    }
+}
+
+flexar::parser! {
+    [[Expr] parxt: TokenType]
+    parse {
+        (Int(x)) => {
+            (Plus), [right: Expr::parse] => (Plus(*x, Box::new(right)));
+        } (else Ok(Expr::Int(*x)))
+    } else Err(E002: parxt.current_token());
+}
+
+flexar::ast! {
+    #[derive(Debug)]
+    pub enum Stmt {
+        Expr(Node<Expr>),
+    }
+}
+
+flexar::parser! {
+    [[Stmt] parxt: TokenType]
+    parse {
+        [expr: Expr::parse] => {
+            (Semi) => (Expr(expr));
+        } (else Err(E003: parxt.current_token()))
+    } else Err(E002: parxt.current_token());
+}
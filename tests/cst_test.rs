@@ -0,0 +1,60 @@
+mod common;
+use flexar::{prelude::*, cst::{Trace, GreenNode, SyntaxNode, SyntaxElement}};
+use common::*;
+
+fn parse(contents: &str) -> (Box<[flexar::token_node::Token<TokenType>]>, SyntaxNode<TokenType>) {
+    let lext = Lext::new(String::from("example"), contents);
+    let file = lext.cursor.pos_start.file;
+    let tokens = TokenType::tokenize(lext);
+    let trace = Trace::default();
+    let mut parxt = Parxt::new(&tokens).traced(&trace);
+    while parxt.current().is_some() {
+        Stmt::parse(&mut parxt).unwrap();
+    }
+    let tree = SyntaxNode::new_root(GreenNode::build(file, &tokens, &trace));
+    (tokens, tree)
+}
+
+#[test]
+fn test_round_trip() {
+    let contents = "\n  1 + 2; // one plus two\n\n\t3 ;\n// trailing comment\n";
+    let (_, tree) = parse(contents);
+    assert_eq!(tree.text(), contents);
+    assert_eq!(tree.text_range(), 0..contents.len());
+}
+
+#[test]
+fn test_trivia_only() {
+    let contents = "  // just a comment\n\n";
+    let (tokens, tree) = parse(contents);
+    assert!(tokens.is_empty());
+    assert_eq!(tree.text(), contents);
+    assert!(matches!(&tree.children()[..], [SyntaxElement::Trivia(text, range)] if **text == *contents && *range == (0..contents.len())));
+}
+
+#[test]
+fn test_trivia() {
+    let (tokens, _) = parse("1 + 2; // comment\n3;");
+    assert_eq!(tokens.len(), 6);
+    assert_eq!(flexar::token_node::Trivia::text(&tokens[3].trivia.trailing), " // comment");
+    assert_eq!(flexar::token_node::Trivia::text(&tokens[4].trivia.leading), "\n");
+}
+
+#[test]
+fn test_tree_shape() {
+    let (_, tree) = parse("1 + 2;");
+    let children = tree.children();
+    let SyntaxElement::Node(stmt) = &children[0] else { panic!("expected a node") };
+    assert_eq!(stmt.kind(), "Stmt");
+
+    let children = stmt.children();
+    let SyntaxElement::Node(expr) = &children[0] else { panic!("expected a node") };
+    assert_eq!(expr.kind(), "Expr");
+    assert_eq!(expr.text(), "1 + 2");
+    let SyntaxElement::Token(semi) = &children[1] else { panic!("expected a token") };
+    assert_eq!(semi.kind(), &TokenType::Semi);
+    assert_eq!(semi.text_range(), 5..6);
+
+    // `1 + 2` is an `Expr` containing the nested `2` `Expr`
+    assert_eq!(expr.children().len(), 3);
+}
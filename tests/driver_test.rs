@@ -0,0 +1,153 @@
+mod common;
+use flexar::{prelude::*, driver::Driver, source_map};
+use common::*;
+
+#[derive(Debug)]
+pub enum Program {
+    Stmts(Box<[Node<Stmt>]>),
+}
+
+flexar::parser! {
+    [[Program] parxt: TokenType]
+    parse_all recover {
+        [stmts: Stmt::parse]* => (Stmts(stmts.into_boxed_slice()));
+    } else Err(E004: parxt.current_token());
+}
+
+#[derive(Debug)]
+pub struct First(Node<Stmt>);
+
+flexar::parser! {
+    [[First] parxt: TokenType]
+    parse_all {
+        [stmt: Stmt::parse] => ((stmt));
+    } else Err(E004: parxt.current_token());
+}
+
+fn driver() -> Driver<TokenType, Stmt> {
+    Driver::new(TokenType::tokenize_with, Stmt::parse)
+}
+
+#[test]
+fn test_run() {
+    let dir = std::env::temp_dir().join(format!("flexar-driver-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let paths = (0..8).map(|i| {
+        let path = dir.join(format!("{i}.fx"));
+        std::fs::write(&path, format!("{i}; {i}\n;; {i}")).unwrap();
+        path
+    }).collect::<Vec<_>>();
+
+    let output = driver().threads(3).run(paths.iter().chain([&dir.join("missing.fx")]));
+    assert_eq!(output.files.len(), 9);
+    assert_eq!(output.io_errors().map(|x| x.0).collect::<Vec<_>>(), [dir.join("missing.fx")]);
+
+    for (i, file) in output.files[..8].iter().enumerate() {
+        assert_eq!(file.path, paths[i]);
+        let parsed = file.result.as_ref().unwrap();
+        assert_eq!(parsed.items.len(), 4);
+        assert_eq!(parsed.tokens[0].position.0.file_name().as_ref(), paths[i].display().to_string());
+    }
+
+    let diagnostics = output.diagnostics();
+    assert_eq!(diagnostics.len(), 16);
+    assert!(diagnostics.iter().map(|x| &*x.id).eq([["E002", "E003"]; 8].into_iter().flatten()));
+    assert_eq!(diagnostics[2].position.0.file_name(), output.files[1].result.as_ref().unwrap().tokens[0].position.0.file_name());
+
+    // the files it read are removed along with the output
+    let (file, error) = (output.files[0].result.as_ref().unwrap().file, diagnostics[0].clone());
+    drop(output);
+    assert!(source_map::with(|x| x.get(file).is_none()));
+    assert!(error.to_string().contains("<removed"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_run_files() {
+    let files = ["1; 2;", "3;"].map(|x| source_map::add_file("memory", x));
+    let parsed = driver().run_files(&files);
+    assert_eq!(parsed.iter().map(|x| x.items.len()).collect::<Vec<_>>(), [2, 1]);
+    assert_eq!(parsed[1].file, files[1]);
+    assert_eq!(parsed[0].errors().count(), 0);
+}
+
+#[test]
+fn test_lex_errors() {
+    let files = ["1; $ 2;", "3; 4"].map(|x| source_map::add_file("memory", x));
+    let parsed = driver().run_files(&files);
+    assert!(parsed[0].tokens.is_empty() && parsed[0].items.is_empty());
+    assert_eq!(parsed[0].errors().map(|x| (&*x.id, x.position.start())).collect::<Vec<_>>(), [("E001", (1, 4))]);
+    assert_eq!(parsed[1].items.len(), 2); // the other files are still parsed
+
+    let dir = std::env::temp_dir().join(format!("flexar-driver-lex-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.fx"), "1 $").unwrap();
+    std::fs::write(dir.join("b.fx"), "2").unwrap();
+    let output = driver().run([dir.join("a.fx"), dir.join("b.fx")]);
+    assert_eq!(output.diagnostics().iter().map(|x| &*x.id).collect::<Vec<_>>(), ["E001", "E003"]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_many_tokens() {
+    let contents = "1; 2;\n".repeat(20_000); // more tokens than fit in a `u16`
+    let parsed = driver().run_files(&[source_map::add_file("large", contents)]).remove(0);
+    assert_eq!(parsed.tokens.len(), 80_000);
+    assert_eq!(parsed.items.len(), 40_000);
+    assert_eq!(parsed.items.last().unwrap().tokens, 79_998..80_000);
+    assert_eq!(parsed.errors().count(), 0);
+}
+
+#[test]
+fn test_checkpoint() {
+    let tokens = TokenType::tokenize(Lext::new("memory".into(), "1; 2"));
+    let mut parxt = Parxt::new(&tokens);
+    let checkpoint = parxt.checkpoint();
+    assert!(Stmt::parse(&mut parxt).is_ok());
+    assert_eq!(parxt.idx(), 2);
+
+    let after = parxt.checkpoint();
+    assert!(Stmt::parse(&mut parxt).is_err());
+    assert_ne!(parxt.checkpoint(), after); // the error was after `2`
+    parxt.rewind(after);
+    assert!(matches!(parxt.current(), Some(TokenType::Int(2))));
+
+    parxt.rewind(checkpoint);
+    assert_eq!((parxt.idx(), checkpoint.idx()), (0, 0));
+    assert!(matches!(parxt.current(), Some(TokenType::Int(1))));
+}
+
+#[test]
+fn test_parse_all() {
+    let lext = Lext::new("memory".into(), "1; 2;\n3;");
+    let eof = lext.eof();
+    let tokens = TokenType::tokenize(lext);
+    let program = Program::parse_all(&tokens, eof).unwrap();
+    let Program::Stmts(stmts) = &*program;
+    assert_eq!(stmts.len(), 3);
+    assert_eq!((program.position.start(), program.position.end()), ((1, 1), (2, 2)));
+
+    // empty input is at the end of its file
+    let lext = Lext::new("memory".into(), "\n\n");
+    let eof = lext.eof();
+    let program = Program::parse_all(&[], eof).unwrap();
+    assert!(matches!(&*program, Program::Stmts(x) if x.is_empty()));
+    assert_eq!((&*program.position.0.file_name(), program.position.start()), ("memory", (3, 1)));
+
+    // it skips tokens until an item parses again, with one error per failure
+    let lext = Lext::new("memory".into(), "1; 2 3 4; ;; 5;");
+    let eof = lext.eof();
+    let tokens = TokenType::tokenize(lext);
+    let errors = Program::parse_all(&tokens, eof).unwrap_err();
+    assert_eq!(errors.iter().map(|x| &*x.id).collect::<Vec<_>>(), ["E003", "E002"]);
+
+    // leftover tokens are an error
+    let lext = Lext::new("memory".into(), "1; 2;");
+    let eof = lext.eof();
+    let tokens = TokenType::tokenize(lext);
+    let errors = First::parse_all(&tokens, eof).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!((&*errors[0].id, errors[0].position.0.ln_idx()), ("E004", 4));
+    assert!(First::parse_all(&tokens[..2], eof).is_ok_and(|x| matches!(&x.node.0.node, Stmt::Expr(Node { node: Expr::Int(1), .. }))));
+    assert_eq!(First::parse_all(&tokens[..1], eof).unwrap_err()[0].id, "E003");
+}
@@ -0,0 +1,134 @@
+#![cfg(feature = "fuzz")]
+mod common;
+use flexar::{prelude::*, fuzz::{Fuzzer, Gen, Grammar, Rng}};
+use common::*;
+
+fn fuzzer() -> Fuzzer<TokenType> {
+    Fuzzer::new(TokenType::tokenize_with)
+        .parser(Stmt::parse)
+        .fragments(["1", "0x", "2.5e", "+", ";", "\"a\\", "\\u{", "//", "\n", "!"])
+}
+
+#[test]
+fn test_fuzz() {
+    let fuzzer = fuzzer();
+    for input in ["", " ", "\n\n", "+", "1 +", "\"abc", "0x", "é 1;", "1; // 💥", "\"\\u{110000}\"", "!"] {
+        assert_eq!(fuzzer.check(input), Ok(()));
+    }
+
+    let failure = fuzzer.check("1 + !!").unwrap_err();
+    assert!(failure.message.contains("lexer panicked: double bang"), "{failure}");
+}
+
+#[test]
+fn test_fuzz_run() {
+    let mut fuzzer = fuzzer().seed(7);
+    let failure = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| fuzzer.run(2000))).unwrap_err();
+    let message = failure.downcast_ref::<String>().unwrap();
+    assert_eq!(message, "lexer panicked: double bang (input: \"!!\")"); // shrunk down to what panics
+
+    let mut fuzzer = Fuzzer::new(TokenType::tokenize_with).parser(Stmt::parse).fragments(["1", "+", ";", "\"", " ", "\n"]);
+    fuzzer.run(500);
+}
+
+#[test]
+fn test_round_trip() {
+    let grammar = Grammar::new()
+        .parser(Stmt::parse)
+        .token("Int", Gen::pick(["0", "12", "0xff", "1_000"]))
+        .token("Plus", Gen::text("+"))
+        .token("Semi", Gen::text(";"))
+        .rule("program", Gen::repeat(Gen::rule("Stmt::parse"), 1, 4));
+
+    let mut rng = Rng::new(3);
+    let programs = (0..50).map(|_| grammar.generate("program", &mut rng)).collect::<Vec<_>>();
+    assert!(programs.iter().any(|x| x.contains('+')));
+    assert!(programs.iter().all(|x| x.ends_with(';')));
+    fuzzer().round_trip(&grammar, "program", 200);
+
+    // rules can go deeper than the max depth until they reach their simplest option
+    let deep = Grammar::new().max_depth(0).parser(Expr::parse).token("Int", Gen::text("1")).token("Plus", Gen::text("+"));
+    assert_eq!(deep.generate("Expr::parse", &mut rng), "1");
+}
+
+#[test]
+#[should_panic(expected = "doesn't have the text of the `Semi` token")]
+fn test_missing_token() {
+    let grammar = Grammar::new().parser(Stmt::parse).token("Int", Gen::text("1")).token("Plus", Gen::text("+"));
+    grammar.generate("Stmt::parse", &mut Rng::new(1));
+}
+
+#[test]
+#[should_panic(expected = "there's nothing to pick from")]
+fn test_empty_pick() {
+    Gen::pick(Vec::<String>::new());
+}
+
+#[test]
+fn test_empty_input() {
+    for input in ["", "  \n\t", "// nothing here"] {
+        let lext = Lext::new("empty.fx".into(), input);
+        let eof = lext.eof();
+        let tokens = TokenType::tokenize(lext);
+        assert!(tokens.is_empty());
+
+        let mut parxt = Parxt::new(&tokens).with_eof(eof);
+        let Err(ParseError { error, .. }) = Stmt::parse(&mut parxt) else { panic!("parsed nothing") };
+        assert_eq!(error.position, eof);
+        assert_eq!(&*error.position.0.file_name(), "empty.fx");
+        assert!(error.to_string().contains("expected number"));
+    }
+
+    // without an eof it still has a position to report
+    let Err(ParseError { error, .. }) = Stmt::parse(&mut Parxt::new(&[])) else { panic!("parsed nothing") };
+    assert!(error.position.is_empty());
+}
+
+flexar::ast! {
+    #[derive(Debug)]
+    pub enum Wrapped {
+        Int(Node<Expr>),
+    }
+}
+
+flexar::parser! {
+    [[Wrapped] parxt: TokenType]
+    parse {
+        [int: hand::int] => (Int(int));
+    } else Err(E002: parxt.current_token());
+}
+
+mod hand {
+    use super::*;
+
+    /// A hand-written rule that doesn't use any `parser!` function
+    pub fn int(parxt: &mut Parxt<'_, TokenType>) -> Result<Node<Expr>, ParseError> {
+        match parxt.current_token() {
+            Some(Token { token_type: TokenType::Int(x), position, .. }) => {
+                parxt.advance();
+                Ok(Node::new(*position, Expr::Int(*x)))
+            },
+            _ => Err(ParseError::new(parxt.idx(), flexar::compiler_error!((E002, parxt.position()) parxt.current_token()))),
+        }
+    }
+}
+
+#[test]
+fn test_hand_written_rule() {
+    // it's named after its code, so its text has to be given by hand
+    let grammar = Grammar::new().parser(Wrapped::parse).rule("hand::int", Gen::text("7"));
+    assert_eq!(grammar.generate("Wrapped::parse", &mut Rng::new(1)), "7");
+}
+
+#[test]
+fn test_repeat() {
+    // a repeat with its bounds the wrong way round is only repeated the minimum amount of times
+    let grammar = Grammar::new().rule("backwards", Gen::Repeat(Box::new(Gen::text("x")), 2, 1));
+    assert_eq!(grammar.generate("backwards", &mut Rng::new(1)), "x x");
+}
+
+#[test]
+#[should_panic(expected = "can't repeat at least 2 and at most 1 times")]
+fn test_backwards_repeat() {
+    Gen::repeat(Gen::text("x"), 2, 1);
+}
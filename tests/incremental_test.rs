@@ -0,0 +1,176 @@
+mod common;
+use std::fmt::{Debug, Display};
+use flexar::{prelude::*, incremental::{Document, EditError, ResumableTokenizer, TextEdit}};
+use common::*;
+
+fn document(contents: &str) -> Document<TokenType, Stmt> {
+    Document::new("example".into(), contents.into(), TokenType::tokenize_from, Stmt::parse)
+}
+
+fn assert_edit(contents: &str, edit: TextEdit, expected: &str) {
+    let mut doc = document(contents);
+    doc.edit(&edit).unwrap();
+    let fresh = document(expected);
+
+    assert_eq!(doc.contents, expected);
+    assert_eq!(format!("{:?}", doc.tokens), format!("{:?}", fresh.tokens));
+    assert_eq!(format!("{:?}", doc.items), format!("{:?}", fresh.items));
+}
+
+#[test]
+fn test_same_line() {
+    assert_edit("1 + 2;\n3 + 4;\n5;", TextEdit::new((2, 5), (2, 6), "42"), "1 + 2;\n3 + 42;\n5;");
+}
+
+#[test]
+fn test_new_lines() {
+    assert_edit("1 + 2;\n3 + 4;\n5;", TextEdit::new((1, 7), (1, 7), "\n\n6;"), "1 + 2;\n\n6;\n3 + 4;\n5;");
+}
+
+#[test]
+fn test_remove_lines() {
+    assert_edit("1 + 2;\n3 + 4;\n5;\n6;", TextEdit::new((1, 4), (3, 1), ""), "1 +5;\n6;");
+}
+
+#[test]
+fn test_merge_tokens() {
+    assert_edit("12 + 3;\n4;", TextEdit::new((1, 3), (1, 6), ""), "123;\n4;");
+}
+
+thread_local! {
+    static PARSED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Counts the items that are parsed on this thread
+fn counted(parxt: &mut Parxt<'_, TokenType>) -> Result<Node<Stmt>, ParseError> {
+    PARSED.set(PARSED.get() + 1);
+    Stmt::parse(parxt)
+}
+
+#[test]
+fn test_reuse() {
+    let mut doc = Document::new("example".into(), "1 + 2;\n3 + 4;\n5;\n6 7;".into(), TokenType::tokenize_from, counted);
+    PARSED.set(0);
+    doc.edit(&TextEdit::new((1, 5), (1, 6), "42")).unwrap();
+    assert_eq!(PARSED.get(), 1); // only the edited item, even though the ones after it moved
+
+    let fresh = document("1 + 42;\n3 + 4;\n5;\n6 7;");
+    assert_eq!(format!("{:?}", doc.items), format!("{:?}", fresh.items));
+    assert_eq!(doc.errors().map(|x| x.position.start()).collect::<Vec<_>>(), [(4, 3)]);
+}
+
+#[test]
+fn test_errors() {
+    let mut doc = document("1 + 2;\n3 4;\n5;");
+    assert_eq!(doc.errors().count(), 1);
+    doc.edit(&TextEdit::new((2, 2), (2, 2), " +")).unwrap();
+    assert_eq!(doc.errors().count(), 0);
+    assert_eq!(doc.items.len(), 3);
+}
+
+#[test]
+fn test_invalid_edits() {
+    let mut doc = document("1 + 2;\n3;");
+    assert_eq!(doc.edit(&TextEdit::new((0, 1), (1, 1), "")), Err(EditError::OutOfRange((0, 1))));
+    assert_eq!(doc.edit(&TextEdit::new((1, 1), (3, 1), "")), Err(EditError::OutOfRange((3, 1))));
+    assert_eq!(doc.edit(&TextEdit::new((1, 8), (1, 8), "")), Err(EditError::OutOfRange((1, 8))));
+    assert_eq!(doc.edit(&TextEdit::new((2, 1), (1, 3), "")), Err(EditError::Inverted((2, 1), (1, 3))));
+    assert_eq!(doc.contents, "1 + 2;\n3;");
+
+    doc.edit(&TextEdit::new((1, 7), (2, 1), " ")).unwrap();
+    assert_eq!(doc.contents, "1 + 2; 3;");
+}
+
+flexar::lexer! {
+    [[Template] lext, current]
+    else flexar::compiler_error!((E001, lext.position()) current).throw();
+
+    token_types {
+        Quote => "\"";
+        Text(val: String) => val;
+        Interp => "${";
+        RBrace => "}";
+        Plus => "+";
+        Ident(val: String) => val;
+    }
+
+    [" \n"] >> trivia;
+    Plus: +;
+    Ident ~ ([alpha]+);
+    '"' child { advance: (); push_mode: Str; done Quote(); };
+    '}' child { advance: (); pop_mode: (); done RBrace(); };
+
+    mode Str {
+        else flexar::compiler_error!((E001, lext.position()) current).throw();
+
+        Text ~ (!['"', '$']+);
+        '"' child { advance: (); pop_mode: (); done Quote(); };
+        '$' child {
+            advance: current;
+            ck (current, '{') { advance: (); push_mode: default; done Interp(); };
+        };
+    };
+}
+
+flexar::lexer! {
+    [[Indented] lext, current]
+    else flexar::compiler_error!((E001, lext.position()) current).throw();
+
+    layout(Indent, Dedent, Newline) else |position| flexar::compiler_error!((E001, position) ' ').throw();
+
+    token_types {
+        Indent => "<indent>";
+        Dedent => "<dedent>";
+        Newline => "<newline>";
+        Colon => ":";
+        Ident(val: String) => val;
+    }
+
+    [" \n"] >> trivia;
+    Colon: :;
+    Ident ~ ([alpha]+);
+}
+
+/// Parses every token as an item of its own
+fn any_token<TT: Display>(parxt: &mut Parxt<'_, TT>) -> Result<Node<TokenItem>, ParseError> {
+    let position = parxt.current_token().expect("only called before the end").position;
+    parxt.advance();
+    Ok(Node::new(position, TokenItem {}))
+}
+
+flexar::ast! {
+    #[derive(Debug)]
+    pub struct TokenItem {}
+}
+
+fn assert_relexed<TT: Display + Debug + PartialEq>(tokenize: ResumableTokenizer<TT>, contents: &str, edit: TextEdit, expected: &str) {
+    let document = |contents: &str| Document::new("example".into(), contents.into(), tokenize, any_token::<TT>);
+    let mut doc = document(contents);
+    doc.edit(&edit).unwrap();
+    let fresh = document(expected);
+
+    assert_eq!(doc.contents, expected);
+    assert_eq!(format!("{:?}", doc.tokens), format!("{:?}", fresh.tokens));
+    assert_eq!(format!("{:?}", doc.items), format!("{:?}", fresh.items));
+}
+
+#[test]
+fn test_modes() {
+    // the lexer restarts in the mode it was in, so the text stays one token
+    assert_relexed(Template::tokenize_from, "a + \"x y z\" + c", TextEdit::new((1, 11), (1, 11), "q"), "a + \"x y zq\" + c");
+    assert_relexed(Template::tokenize_from, "a + \"x ${b} z\" + c", TextEdit::new((1, 10), (1, 11), "b + d"), "a + \"x ${b + d} z\" + c");
+
+    // the tokens after an edit are only reused once the lexer is back in the same mode
+    assert_relexed(Template::tokenize_from, "a + \"x\" + b + \"y\"", TextEdit::new((1, 5), (1, 6), ""), "a + x\" + b + \"y\"");
+    assert_relexed(Template::tokenize_from, "\"a\" + b", TextEdit::new((1, 3), (1, 3), "\" + \""), "\"a\" + \"\" + b");
+}
+
+#[test]
+fn test_layout() {
+    let contents = "if a:\n    b\n    c\nd\n";
+    assert_relexed(Indented::tokenize_from, contents, TextEdit::new((2, 5), (2, 6), "bb"), "if a:\n    bb\n    c\nd\n");
+    assert_relexed(Indented::tokenize_from, contents, TextEdit::new((3, 1), (3, 1), "  "), "if a:\n    b\n      c\nd\n");
+    assert_relexed(Indented::tokenize_from, contents, TextEdit::new((3, 1), (3, 5), ""), "if a:\n    b\nc\nd\n");
+    assert_relexed(Indented::tokenize_from, contents, TextEdit::new((2, 6), (2, 6), ":\n        e"), "if a:\n    b:\n        e\n    c\nd\n");
+    assert_relexed(Indented::tokenize_from, contents, TextEdit::new((4, 1), (4, 1), "    "), "if a:\n    b\n    c\n    d\n");
+}
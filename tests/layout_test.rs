@@ -0,0 +1,70 @@
+use flexar::{prelude::*, lexers, layout::{Layout, LayoutToken}, token_node::Trivia};
+
+flexar::compiler_error! {
+    [[Define] CompileErrors]
+    (E001) "invalid character": "`", "` is an invalid character";
+    (E002) "inconsistent indentation": "indentation doesn't match any outer level";
+}
+
+flexar::lexer! {
+    [[TokenType] lext, current]
+    else flexar::compiler_error!((E001, lext.position()) current).throw();
+
+    layout(Indent, Dedent, Newline) else |position| flexar::compiler_error!((E002, position)).throw();
+
+    token_types {
+        Indent => "<indent>";
+        Dedent => "<dedent>";
+        Newline => "<newline>";
+        Colon => ":";
+        Ident(val: String) => val;
+    }
+
+    [" \t\n"] >> trivia;
+    '#' child { if (lexers::line_comment(&mut child, "#")) { trivia: (); }; };
+    Colon: :;
+    Ident ~ ([alpha]+);
+}
+
+fn tokenize(contents: &str) -> Box<[Token<TokenType>]> {
+    TokenType::tokenize(Lext::new("example".into(), contents))
+}
+
+#[test]
+fn test_layout() {
+    let contents = "if a:\n    b\n    # comment\n    if c:\n        d\n\ne\n";
+    let tokens = tokenize(contents);
+    assert_eq!(tokens.iter().map(|x| x.token_type.to_string()).collect::<Vec<_>>(), [
+        "if", "a", ":", "<newline>",
+        "<indent>", "b", "<newline>",
+        "if", "c", ":", "<newline>",
+        "<indent>", "d", "<newline>",
+        "<dedent>", "<dedent>", "e", "<newline>",
+    ]);
+
+    // the layout tokens are zero-width, so the source can still be rebuilt from the tokens
+    let text = tokens.iter()
+        .map(|x| format!("{}{}{}", Trivia::text(&x.trivia.leading), x.position.text(), Trivia::text(&x.trivia.trailing)))
+        .collect::<String>();
+    assert_eq!(text, contents);
+}
+
+#[test]
+fn test_continued_lines() {
+    let tokens = tokenize("a #x\n  # only a comment\nb");
+    assert_eq!(tokens.iter().map(|x| x.token_type.to_string()).collect::<Vec<_>>(), ["a", "<newline>", "b", "<newline>"]);
+}
+
+#[test]
+fn test_inconsistent() {
+    assert!(std::panic::catch_unwind(|| tokenize("a\n    b\n  c")).is_err());
+
+    let mut lext = Lext::new("example".into(), "a\n    b\n  c\n\td");
+    let mut layout = Layout::default();
+    let errors = [(1, 1), (2, 5), (3, 3), (4, 2)].map(|(ln, ln_idx)| {
+        lext.seek(ln, ln_idx);
+        layout.before(&lext.position()).1.map(|x| (x.start().1, x.end().1))
+    });
+    assert_eq!(errors, [None, None, Some((1, 2)), Some((1, 1))]);
+    assert_eq!(layout.finish().into_iter().map(|x| x.0).collect::<Vec<_>>(), [LayoutToken::Newline, LayoutToken::Dedent]);
+}
@@ -1,4 +1,4 @@
-use flexar::{lext::Lext, flext::Flext, token_node::Token};
+use flexar::{compile_error::CompileError, lext::Lext, flext::Flext, token_node::Token, highlight::{self, Format, TokenKind}};
 
 flexar::compiler_error! {
     [[Define] CompileErrors]
@@ -10,19 +10,30 @@ flexar::lexer! {
     [[TokenType] lext, current, 'cycle]
     else flexar::compiler_error!((E001, lext.position()) current).throw();
 
+    keywords(Ident) {
+        Let => "let";
+        Fn => "fn";
+    }
+
     token_types {
-        Slash => "/";
-        Plus => "+";
-        LParen => "(";
-        RParen => ")";
-        EE => "==";
-        EEE => "===";
-        EQ => "=";
+        [operator] Slash => "/";
+        [operator] Plus => "+";
+        [punctuation] LParen => "(";
+        [punctuation] RParen => ")";
+        [operator] EE => "==";
+        [operator] EEE => "===";
+        [operator] EQ => "=";
         Dot => ".";
         Colon => ":";
-        Str(val: String) => val;
-        Int(val: u32) => val;
-        Float(val: f32) => val;
+        [string] Str(val: String) => val;
+        [number] Int(val: u32) => val;
+        [number] Float(val: f32) => val;
+        [identifier] Ident(val: String) => val;
+        [number] Hex(val: String) => val;
+        [identifier] Label(val: String) => val;
+        [operator] Lt => "<";
+        [operator] Shl => "<<";
+        [operator] ShlEq => "<<=";
     }
 
     Slash: /;
@@ -33,10 +44,14 @@ flexar::lexer! {
     Colon: :;
     [" \n\t"] >> ({ lext.advance(); lext = lext.spawn(); continue 'cycle; });
 
-    // `=` stuff
-    EEE: (= = =);
-    EE: (= =);
+    // `=` stuff (longest match, so order doesn't matter)
     EQ: =;
+    EE: (= =);
+    EEE: (= = =);
+
+    // patterns
+    Hex ~ ('0' ('x' | "X") ['0'..='9', 'a'..='f', "ABCDEF"]+);
+    Label ~ ('@' [xid_start, '_'] [xid_continue]*);
     '"' child {
         { child.advance() };
         set string { String::new() };
@@ -49,6 +64,17 @@ flexar::lexer! {
         };
         throw E002(child.spawn().position());
     };
+    ['a'..='z'] child {
+        eat_while ident ['a'..='z', digit];
+        done Ident(ident);
+    };
+    '<' child {
+        eat ("<<=") { done ShlEq(); };
+        peek next (1);
+        ck (next, '<') { advance: (); advance: (); done Shl(); };
+        advance: ();
+        done Lt();
+    };
     ["0123456789"] child {
         set number { String::new() };
         set dot false;
@@ -160,10 +186,164 @@ fn test_float() {
     ]);
 }
 
+#[test]
+fn test_pattern() {
+    let contents = "0x1fA 0X2 @héllo_1 0xg x2";
+    let tokens = TokenType::tokenize(Lext::new(String::from("example"), contents));
+    use TokenType as L;
+    assert_tokens(&tokens, &[
+        L::Hex("0x1fA".into()),
+        L::Hex("0X2".into()),
+        L::Label("@héllo_1".into()),
+        L::Int(0),
+        L::Ident("xg".into()),
+        L::Ident("x2".into()),
+    ]);
+    assert_eq!(tokens.len(), 6);
+}
+
+#[test]
+fn test_lookahead() {
+    let contents = "< << <<= <<<";
+    let tokens = TokenType::tokenize(Lext::new(String::from("example"), contents));
+    use TokenType as L;
+    assert_tokens(&tokens, &[L::Lt, L::Shl, L::ShlEq, L::Shl, L::Lt]);
+    assert_eq!(tokens.len(), 5);
+}
+
+#[test]
+fn test_keywords() {
+    let contents = "let x = fn letter";
+    let tokens = TokenType::tokenize(Lext::new(String::from("example"), contents));
+    use TokenType as L;
+    assert_tokens(&tokens, &[
+        L::Let,
+        L::Ident("x".into()),
+        L::EQ,
+        L::Fn,
+        L::Ident("letter".into()),
+    ]);
+    assert_eq!(TokenType::keyword("fn"), Some(L::Fn));
+    assert_eq!(L::Let.kind(), TokenKind::Keyword);
+}
+
+#[test]
+fn test_kind() {
+    assert_eq!(TokenType::Plus.kind(), TokenKind::Operator);
+    assert_eq!(TokenType::Str("hi".into()).kind(), TokenKind::String);
+    assert_eq!(TokenType::Dot.kind(), TokenKind::Other);
+}
+
+#[test]
+fn test_highlight() {
+    let contents = "(1 + \"<a>\")";
+    let tokens = TokenType::tokenize(Lext::new(String::from("example"), contents));
+    assert_eq!(highlight::highlight(contents, &tokens, Format::Html), concat!(
+        "<span class=\"punctuation\">(</span><span class=\"number\">1</span> <span class=\"operator\">+</span> ",
+        "<span class=\"string\">&quot;&lt;a&gt;&quot;</span><span class=\"punctuation\">)</span>",
+    ));
+}
+
 fn assert_tokens(tokens: &[Token<TokenType>], expected: &[TokenType]) {
     tokens.iter()
         .enumerate()
         .for_each(|(i, x)| if x.token_type != expected[i] {
             panic!("Expected: {expected:?}\nGot: {:?}", tokens.iter().map(|x| &x.token_type).collect::<Box<[&TokenType]>>())
         });
-}
\ No newline at end of file
+}
+flexar::lexer! {
+    [[Template] lext, current]
+    else flexar::compiler_error!((E001, lext.position()) current).throw();
+
+    token_types {
+        Quote => "\"";
+        Text(val: String) => val;
+        Interp => "${";
+        RBrace => "}";
+        Plus => "+";
+        Ident(val: String) => val;
+    }
+
+    [" \n"] >> trivia;
+    Plus: +;
+    Ident ~ ([alpha]+);
+    '"' child { advance: (); push_mode: Str; done Quote(); };
+    '}' child {
+        pop_mode else { throw E001(child.position(), '}'); };
+        advance: ();
+        done RBrace();
+    };
+
+    mode Str {
+        else flexar::compiler_error!((E001, lext.position()) current).throw();
+
+        Text ~ (!['"', '$']+);
+        '"' child { advance: (); pop_mode: (); done Quote(); };
+        '$' child {
+            advance: current;
+            ck (current, '{') { advance: (); push_mode: default; done Interp(); };
+        };
+    };
+}
+
+#[test]
+fn test_modes() {
+    let contents = "a + \"x ${b + \"y\"} z\" + c";
+    let tokens = Template::tokenize(Lext::new(String::from("example"), contents));
+    use Template as T;
+    assert_eq!(tokens.iter().map(|x| x.token_type.clone()).collect::<Vec<_>>(), [
+        T::Ident("a".into()),
+        T::Plus,
+        T::Quote,
+        T::Text("x ".into()),
+        T::Interp,
+        T::Ident("b".into()),
+        T::Plus,
+        T::Quote,
+        T::Text("y".into()),
+        T::Quote,
+        T::RBrace,
+        T::Text(" z".into()),
+        T::Quote,
+        T::Plus,
+        T::Ident("c".into()),
+    ]);
+
+    // a `}` outside of any mode doesn't have a mode to go back to
+    let error = CompileError::catch(|| Template::tokenize(Lext::new(String::from("example"), "a }"))).unwrap_err();
+    assert_eq!((&*error.id, error.position.start()), ("E001", (1, 3)));
+}
+
+flexar::lexer! {
+    [[Ordered] lext, current]
+    else flexar::compiler_error!((E001, lext.position()) current).throw();
+
+    token_types {
+        Custom => "custom";
+        Eq => "=";
+        EqEq => "==";
+        Plus => "+";
+        Inc => "++";
+    }
+
+    [" \n"] >> trivia;
+    '=' child { advance: (); done Custom(); };
+    Eq: =;
+    EqEq: (= =);
+    Plus: +;
+    Inc: (+ +);
+    '+' child { advance: (); done Custom(); };
+}
+
+#[test]
+fn test_rule_order() {
+    // a detailed rule declared before a literal wins, and one declared after it never matches
+    let tokens = Ordered::tokenize(Lext::new(String::from("example"), "== + ++"));
+    use Ordered as O;
+    assert_eq!(tokens.iter().map(|x| x.token_type.clone()).collect::<Vec<_>>(), [
+        O::Custom,
+        O::Custom,
+        O::Plus,
+        O::Inc,
+    ]);
+}
@@ -0,0 +1,92 @@
+use flexar::{prelude::*, compile_error::CompileError, lexers::{self, LexErrorKind, Number}};
+
+flexar::lexer! {
+    [[TokenType] lext, current]
+    else panic!("unexpected character `{current}`");
+
+    token_types {
+        Slash => "/";
+        Num(val: Number) => val;
+        Str(val: String) => val;
+    }
+
+    [" \n\t"] >> trivia;
+    [digit] child {
+        set number { lexers::number(&mut child).unwrap_or_else(|x| CompileError::from(x).throw()) };
+        done Num(number);
+    };
+    '"' child {
+        set string { lexers::string(&mut child, '"').unwrap_or_else(|x| CompileError::from(x).throw()) };
+        done Str(string);
+    };
+    / child {
+        if (lexers::line_comment(&mut child, "//")) { trivia: (); };
+        if (lexers::block_comment(&mut child, "/*", "*/", true).unwrap_or_else(|x| CompileError::from(x).throw())) { trivia: (); };
+        advance: ();
+        done Slash();
+    };
+}
+
+fn lext(contents: &str) -> Lext {
+    Lext::new("example".into(), contents)
+}
+
+fn error_at(kind: LexErrorKind, result: Result<impl std::fmt::Debug, lexers::LexError>, start: usize, end: usize) {
+    let error = result.unwrap_err();
+    assert_eq!(error.kind, kind);
+    assert_eq!((error.position.start().1, error.position.end().1), (start, end));
+}
+
+#[test]
+fn test_numbers() {
+    let tokens = TokenType::tokenize(lext("12 1_000 0xff 0o17 0b1010 1.5 2e3 1.25E-2 7/2"));
+    use TokenType as T;
+    assert_eq!(tokens.iter().map(|x| x.token_type.clone()).collect::<Vec<_>>(), [
+        T::Num(Number::Int(12)),
+        T::Num(Number::Int(1000)),
+        T::Num(Number::Int(255)),
+        T::Num(Number::Int(15)),
+        T::Num(Number::Int(10)),
+        T::Num(Number::Float(1.5)),
+        T::Num(Number::Float(2000.0)),
+        T::Num(Number::Float(0.0125)),
+        T::Num(Number::Int(7)),
+        T::Slash,
+        T::Num(Number::Int(2)),
+    ]);
+}
+
+#[test]
+fn test_number_errors() {
+    error_at(LexErrorKind::MissingDigits, lexers::number(&mut lext("0x ")), 1, 2);
+    error_at(LexErrorKind::InvalidDigit('2'), lexers::number(&mut lext("0b102")), 5, 5);
+    error_at(LexErrorKind::MissingExponent, lexers::number(&mut lext("1e+ ")), 2, 3);
+    error_at(LexErrorKind::Overflow, lexers::number(&mut lext("99999999999999999999")), 1, 20);
+}
+
+#[test]
+fn test_strings() {
+    let tokens = TokenType::tokenize(lext(r#""a\tb\"c\x41\u{1F600}""#));
+    assert_eq!(tokens[0].token_type, TokenType::Str("a\tb\"cA\u{1F600}".into()));
+    assert_eq!(tokens.len(), 1);
+
+    error_at(LexErrorKind::InvalidEscape, lexers::string(&mut lext(r#""ab\q""#), '"'), 4, 5);
+    error_at(LexErrorKind::InvalidEscape, lexers::string(&mut lext(r#""\u{110000}""#), '"'), 2, 11);
+    error_at(LexErrorKind::UnclosedString, lexers::string(&mut lext(r#""abc"#), '"'), 1, 1);
+}
+
+#[test]
+fn test_comments() {
+    let tokens = TokenType::tokenize(lext("1 // two\n/* 3 /* 4 */ 5 */ 6 / 7"));
+    use TokenType as T;
+    assert_eq!(tokens.iter().map(|x| x.token_type.clone()).collect::<Vec<_>>(), [
+        T::Num(Number::Int(1)),
+        T::Num(Number::Int(6)),
+        T::Slash,
+        T::Num(Number::Int(7)),
+    ]);
+
+    let mut unclosed = lext("/* a /* b */");
+    error_at(LexErrorKind::UnclosedComment, lexers::block_comment(&mut unclosed, "/*", "*/", true), 1, 2);
+    assert!(!lexers::line_comment(&mut lext("/ x"), "//"));
+}
@@ -0,0 +1,45 @@
+use flexar::prelude::*;
+
+#[test]
+fn test_lookahead() {
+    let mut lext = Lext::new("example".into(), "let x\ny");
+    assert_eq!(lext.peek(0), Some('l'));
+    assert_eq!(lext.peek(2), Some('t'));
+    assert_eq!(lext.peek(5), Some('\n'));
+    assert_eq!(lext.peek(9), None);
+
+    assert!(lext.starts_with("let "));
+    assert!(!lext.starts_with("lex"));
+    assert!(!lext.eat("lex"));
+    assert_eq!(lext.current, Some('l'));
+
+    assert!(lext.eat("let"));
+    assert_eq!(lext.eat_while(char::is_whitespace), " ");
+    assert_eq!(lext.eat_while(|x| x != 'y'), "x\n");
+    assert_eq!(lext.current, Some('y'));
+    assert_eq!(lext.eat_while(|_| true), "y\n");
+    assert_eq!(lext.current, None);
+}
+
+#[test]
+fn test_sources() {
+    let text = "a\nbé\n";
+    let shared: std::sync::Arc<str> = text.into();
+    let lexts = [
+        Lext::new("example".into(), text),
+        Lext::from_source("example".into(), text),
+        Lext::from_source("example".into(), shared.clone()),
+        Lext::from_bytes("example".into(), text.as_bytes().to_vec()).unwrap(),
+        Lext::from_reader("example".into(), text.as_bytes()).unwrap(),
+    ];
+    for mut lext in lexts {
+        assert_eq!(lext.eat_while(|_| true), "a\nbé\n\n");
+        assert_eq!(lext.position().1.ln(), 3);
+    }
+    assert!(Lext::from_bytes("example".into(), vec![0xff]).is_err());
+
+    let repl = Lext::virtual_file("1 + 2");
+    let other = Lext::virtual_file(shared);
+    assert!(repl.cursor.pos_end.file_name().starts_with("<virtual "));
+    assert_ne!(repl.cursor.pos_end.file_name(), other.cursor.pos_end.file_name());
+}
@@ -0,0 +1,72 @@
+#![cfg(feature = "lsp")]
+mod common;
+use flexar::lsp::{self, Server};
+use serde_json::{json, Value};
+use common::*;
+
+fn run(messages: &[Value]) -> Vec<Value> {
+    let mut input = Vec::new();
+    messages.iter().for_each(|x| lsp::write_message(&mut input, x).unwrap());
+
+    let mut output = Vec::new();
+    Server::new(TokenType::tokenize_from, Stmt::parse)
+        .serve(&mut input.as_slice(), &mut output)
+        .unwrap();
+
+    let mut output = output.as_slice();
+    std::iter::from_fn(|| lsp::read_message(&mut output).unwrap()).collect()
+}
+
+#[test]
+fn test_diagnostics() {
+    let uri = "file:///example.fx";
+    let output = run(&[
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+        json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+            "textDocument": { "uri": uri, "languageId": "fx", "version": 1, "text": "1;\n2 3;" },
+        }}),
+        json!({ "jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+            "textDocument": { "uri": uri, "version": 2 },
+            "contentChanges": [{ "range": { "start": { "line": 1, "character": 1 }, "end": { "line": 1, "character": 1 } }, "text": " +" }],
+        }}),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ]);
+
+    assert_eq!(output.len(), 4);
+    assert_eq!(output[0]["result"]["capabilities"]["textDocumentSync"], 2);
+
+    let diagnostics = &output[1]["params"]["diagnostics"];
+    assert_eq!(output[1]["params"]["uri"], uri);
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(diagnostics[0]["code"], "E003");
+    assert_eq!(diagnostics[0]["range"], json!({ "start": { "line": 1, "character": 2 }, "end": { "line": 1, "character": 3 } }));
+
+    assert_eq!(output[2]["params"]["diagnostics"], json!([]));
+    assert_eq!(output[3], json!({ "jsonrpc": "2.0", "id": 2, "result": null }));
+}
+
+#[test]
+fn test_lexer_errors_and_utf16() {
+    let uri = "file:///emoji.fx";
+    let output = run(&[
+        json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+            "textDocument": { "uri": uri, "languageId": "fx", "version": 1, "text": "1;\n💥2 3;" },
+        }}),
+        json!({ "jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+            "textDocument": { "uri": uri, "version": 2 },
+            "contentChanges": [{ "range": { "start": { "line": 1, "character": 0 }, "end": { "line": 1, "character": 2 } }, "text": "4;" }],
+        }}),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ]);
+
+    // `💥` is two UTF-16 code units
+    let diagnostics = &output[0]["params"]["diagnostics"];
+    assert_eq!(diagnostics[0]["code"], "E001");
+    assert_eq!(diagnostics[0]["range"], json!({ "start": { "line": 1, "character": 0 }, "end": { "line": 1, "character": 2 } }));
+
+    let diagnostics = &output[1]["params"]["diagnostics"];
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(diagnostics[0]["code"], "E003");
+    assert_eq!(diagnostics[0]["range"], json!({ "start": { "line": 1, "character": 4 }, "end": { "line": 1, "character": 5 } }));
+}
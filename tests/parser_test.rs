@@ -0,0 +1,100 @@
+use flexar::{lext::Lext, parxt::Parxt, token_node::Node, flext::Flext, token_node::{Token, TokenToString, Trivia}};
+
+flexar::compiler_error! {
+    [[Define] CompileErrors]
+    (E001) "invalid character": "`", "` is an invalid character";
+    (E003) "expected end of file": "expected end of file, found `", "`.";
+}
+
+flexar::lexer! {
+    [[Ended] lext, current]
+    else flexar::compiler_error!((E001, lext.position()) current).throw();
+    eof(Eof);
+
+    token_types {
+        Int(val: u32) => val;
+    }
+
+    [" \n"] >> trivia;
+    Int ~ ([digit]+);
+}
+
+#[derive(Debug)]
+pub struct Ints(Vec<u32>);
+
+flexar::parser! {
+    [[Ints] parxt: Ended]
+    parse {
+        (Eof) => ((Vec::new()));
+        (Int(x)), [rest: Ints::parse] => [{
+            let mut rest = rest;
+            rest.node.0.insert(0, *x);
+            rest
+        }];
+    } else Err(E003: parxt.current_token());
+}
+
+#[test]
+fn test_eof() {
+    let lext = Lext::new(String::from("example"), "1 2\n3 \n\n");
+    let eof = lext.eof();
+    let tokens = Ended::tokenize(lext);
+    assert_eq!(tokens.iter().map(|x| x.token_type.clone()).collect::<Vec<_>>(), [Ended::Int(1), Ended::Int(2), Ended::Int(3), Ended::Eof]);
+
+    let last = tokens.last().unwrap();
+    assert_eq!(last.position, eof);
+    assert_eq!(last.position.start(), (4, 1));
+    assert_eq!(Trivia::text(&last.trivia.leading), " \n\n");
+
+    let ints = Ints::parse(&mut Parxt::new(&tokens)).unwrap();
+    assert_eq!(ints.node.0, [1, 2, 3]);
+
+    // empty files still get an `Eof` token
+    let tokens = Ended::tokenize(Lext::new(String::from("example"), ""));
+    assert!(matches!(&*tokens, [Token { token_type: Ended::Eof, .. }]));
+    assert!(matches!(Ints::parse(&mut Parxt::new(&tokens)), Ok(Node { node: Ints(x), .. }) if x.is_empty()));
+
+    // stopping early leaves it out
+    let tokens = Ended::tokenize_with(Lext::new(String::from("example"), "1 2"), &mut |x| x.token_type == Ended::Int(2));
+    assert_eq!(tokens.len(), 1);
+}
+
+#[test]
+fn test_parse_error() {
+    let contents = (1..=300).map(|x| x.to_string()).collect::<Vec<_>>().join(" ");
+    let tokens = Ended::tokenize(Lext::new(String::from("example"), &contents));
+    assert!(Ints::parse(&mut Parxt::new(&tokens)).is_ok());
+
+    // the failure furthest through the tokens wins, however deep it is
+    let error = Ints::parse(&mut Parxt::new(&tokens[..300])).unwrap_err();
+    assert_eq!(error.furthest, 300);
+    assert_eq!(error.expected, ["Eof", "Int"]);
+    assert_eq!(error.error.id, "E003");
+}
+
+#[derive(Debug)]
+pub struct Total(u32);
+
+flexar::parser! {
+    [[Total] parxt: Ended]
+    parse_all {
+        [ints: Ints::int]*, (Eof) => ((ints.iter().map(|x| x.node.0[0]).sum()));
+    } else Err(E003: parxt.current_token());
+}
+
+flexar::parser! {
+    [[Ints] parxt: Ended]
+    int {
+        (Int(x)) => ((vec![*x]));
+    } else Err(E003: parxt.current_token());
+}
+
+#[test]
+fn test_parse_all_eof() {
+    let lext = Lext::new(String::from("example"), "1 2\n3\n");
+    let eof = lext.eof();
+    let tokens = Ended::tokenize(lext);
+    assert_eq!(Total::parse_all(&tokens, eof).unwrap().node.0, 6);
+    assert_eq!(Total::parse_all(&tokens[..3], eof).unwrap().node.0, 6); // stopped before the end
+    assert_eq!(Total::parse_all(&tokens[3..], eof).unwrap().node.0, 0);
+}
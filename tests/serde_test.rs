@@ -0,0 +1,82 @@
+#![cfg(feature = "serde")]
+use flexar::{prelude::*, compile_error::CompileError, cursor::Cursor, source_map};
+
+flexar::lexer! {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    [[TokenType] lext, current]
+    else panic!("unexpected character `{current}`");
+
+    token_types {
+        Plus => "+";
+        Word(val: String) => val;
+    }
+
+    Plus: +;
+    [" \n\t"] >> trivia;
+    [alpha] child {
+        set word { child.eat_while(|x| x.is_alphabetic()) };
+        done Word(word);
+    };
+}
+
+#[test]
+fn test_tokens() {
+    let file = source_map::add_file("serde.fx", "é + ab\n  cd");
+    let tokens = TokenType::tokenize(Lext::from_file(file));
+    let json = serde_json::to_value(&tokens[3]).unwrap();
+
+    assert_eq!(json["token_type"], serde_json::json!({ "Word": "cd" }));
+    assert_eq!(json["position"], serde_json::json!({
+        "file": "serde.fx",
+        "hash": format!("{:016x}", source_map::file(file).hash()),
+        "start": { "ln": 2, "col": 3, "offset": 10 },
+        "end": { "ln": 2, "col": 5, "offset": 12 },
+    }));
+    assert_eq!(json["trivia"]["leading"][0]["text"], "\n  ");
+
+    let back: Box<[Token<TokenType>]> = serde_json::from_str(&serde_json::to_string(&tokens).unwrap()).unwrap();
+    assert_eq!(back.len(), tokens.len());
+    for (a, b) in back.iter().zip(tokens.iter()) {
+        assert_eq!((&a.token_type, a.position), (&b.token_type, b.position));
+    }
+}
+
+#[test]
+fn test_errors() {
+    let position = Lext::from_source("errors.fx".into(), "abc").position();
+    let error = CompileError::new("E001", "some error", "message".into(), position);
+    let node = Node::new(position, 12);
+
+    let error: CompileError = serde_json::from_str(&serde_json::to_string(&error).unwrap()).unwrap();
+    assert_eq!((&*error.id, &*error.error_type, error.position), ("E001", "some error", position));
+    let node: Node<i32> = serde_json::from_str(&serde_json::to_string(&node).unwrap()).unwrap();
+    assert_eq!((node.node, node.position), (12, position));
+
+    let unknown = r#"{"file":"missing.fx","hash":"0000000000000000","start":{"ln":1,"col":1,"offset":0},"end":{"ln":1,"col":1,"offset":0}}"#;
+    assert!(serde_json::from_str::<Position>(unknown).is_err());
+    let malformed = unknown.replace("missing.fx", "errors.fx").replace("0000000000000000", "not a hash");
+    assert!(serde_json::from_str::<Position>(&malformed).unwrap_err().to_string().contains("`not a hash` isn't a file hash"));
+}
+
+#[test]
+fn test_same_names() {
+    let first = source_map::add_file("same.fx", "one two");
+    let second = source_map::add_file("same.fx", "three four");
+    let position = Position(Cursor::from_file(second).at(6), Cursor::from_file(second).at(10));
+
+    // the contents tell files with the same name apart
+    let json = serde_json::to_string(&position).unwrap();
+    assert_eq!(serde_json::from_str::<Position>(&json).unwrap(), position);
+    assert_ne!(serde_json::from_str::<Position>(&json).unwrap().0.file, first);
+
+    // and the offsets have to match the lines and columns
+    let mut value = serde_json::to_value(position).unwrap();
+    value["start"]["offset"] = 5.into();
+    assert!(serde_json::from_value::<Position>(value.clone()).unwrap_err().to_string().contains("isn't at offset 5"));
+    value["start"]["offset"] = 42.into();
+    assert!(serde_json::from_value::<Position>(value).is_err());
+
+    // a file that changed since isn't found
+    source_map::update_file(second, "five six");
+    assert!(serde_json::from_str::<Position>(&json).is_err());
+}
@@ -0,0 +1,26 @@
+mod common;
+use flexar::testing::Snapshots;
+use common::*;
+
+fn snapshots(dir: impl Into<std::path::PathBuf>) -> Snapshots<TokenType> {
+    Snapshots::new(dir, TokenType::tokenize_with).parser(Stmt::parse)
+}
+
+#[test]
+fn test_snapshots() {
+    snapshots(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots")).run();
+}
+
+#[test]
+fn test_mismatch() {
+    let dir = std::env::temp_dir().join(format!("flexar-snapshots-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.fx"), "1;").unwrap();
+    std::fs::write(dir.join("a.fx.snap"), "=== tokens ===\n1:1-1:1 Int(2)\n1:2-1:2 Semi\n").unwrap();
+    std::fs::write(dir.join("b.fx"), "2;").unwrap();
+
+    let failures = snapshots(&dir).bless(false).check().unwrap_err();
+    assert!(failures.contains("- 1:1-1:1 Int(2)\n+ 1:1-1:1 Int(1)\n  1:2-1:2 Semi\n+ === ast ===\n"), "{failures}");
+    assert!(failures.contains("b.fx.snap` is missing"), "{failures}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
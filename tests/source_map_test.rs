@@ -0,0 +1,82 @@
+use flexar::{compile_error::CompileError, cursor::Position, flext::Flext, lext::Lext, source_map::{self, SourceMap}};
+
+#[test]
+fn test_source_map() {
+    let mut map = SourceMap::new();
+    let main = map.add("main.fx", "let x = 1;\nprint x;");
+    let lib = map.add("lib.fx", "fn f() {}");
+    assert_ne!(main, lib);
+    assert_eq!(map.find("lib.fx"), Some(lib));
+    assert_eq!(map.get(main).unwrap().contents.line(1), Some("print x;"));
+
+    map.update(main, "print 2;");
+    assert_eq!(&*map.get(main).unwrap().name, "main.fx");
+    assert_eq!(map.get(main).unwrap().contents.line(0), Some("print 2;"));
+    assert_eq!(map.files().count(), 2);
+}
+
+#[test]
+fn test_positions() {
+    assert!(std::mem::size_of::<Position>() <= 16);
+
+    let file = source_map::add_file("shared.fx", "abc\ndef");
+    let (mut first, second) = (Lext::from_file(file), Lext::from_file(file));
+    first.advance();
+    let position = first.position();
+    let copy = position;
+
+    assert_eq!(copy, position);
+    assert_eq!(position.text(), "ab");
+    assert_eq!(&*position.0.file_name(), "shared.fx");
+    assert_eq!(position.0.file, second.cursor.pos_start.file);
+    assert_eq!(position.0.get_ln().as_deref(), Some("abc"));
+
+    source_map::update_file(file, "xyz");
+    assert_eq!(position.text(), "xy");
+}
+
+#[test]
+fn test_long_lines() {
+    let contents = format!("{}\nb", "a".repeat(70_000));
+    let mut lext = Lext::from_source("long.fx".into(), contents);
+    lext.seek(2, 1);
+    assert_eq!(lext.current, Some('b'));
+    assert_eq!(lext.position().start(), (2, 1));
+    assert_eq!(lext.position().0.offset(), 70_001);
+
+    lext.revance();
+    assert_eq!(lext.cursor.pos_end.location(), (1, 70_001));
+}
+
+#[test]
+fn test_remove_file() {
+    let file = source_map::add_file("removed.fx", "abc");
+    assert!(source_map::with(|x| x.get(file).is_some()));
+    assert_eq!(source_map::remove_file(file).map(|x| x.name.clone()).as_deref(), Some("removed.fx"));
+    assert!(source_map::with(|x| x.get(file).is_none() && x.files().all(|(id, _)| id != file)));
+}
+
+#[test]
+fn test_removed_positions() {
+    let file = source_map::add_file("closed.fx", "abc\ndef");
+    let mut lext = Lext::from_file(file);
+    lext.seek(2, 2);
+    let error = CompileError::new("E001", "example", "example error".into(), lext.position());
+    source_map::remove_file(file);
+
+    // errors that outlive their file can still be printed
+    assert!(error.to_string().contains("<removed closed.fx>") && error.to_string().contains("example error"), "{error}");
+    assert_eq!(format!("{:?}", error.position), "<position: <removed closed.fx>:1:1 - <removed closed.fx>:1:1>");
+    assert_eq!(error.position.text(), "");
+    assert!(source_map::file(file).is_removed());
+    assert_eq!(source_map::remove_file(file).map(|_| ()), None);
+}
+
+#[test]
+fn test_oneline() {
+    let first = Position::new_oneline("oneline.fx", "abc", Some((2, 3)));
+    let second = Position::new_oneline("oneline.fx", "abc", None);
+    assert_eq!(first.0.file, second.0.file); // the same line isn't added twice
+    assert_eq!((first.text(), second.text()), ("bc".into(), "abc".into()));
+    assert_ne!(Position::new_oneline("oneline.fx", "xyz", None).0.file, first.0.file);
+}
@@ -0,0 +1,41 @@
+use flexar::{prelude::*, compile_error::CompileError, cst::GreenNode, incremental::Document};
+
+flexar::lexer! {
+    [[TokenType] lext, current]
+    else panic!("unexpected character `{current}`");
+
+    token_types {
+        Word(val: String) => val;
+    }
+
+    [" \n\t"] >> trivia;
+    [alpha] child {
+        set word { child.eat_while(|x| x.is_alphabetic()) };
+        done Word(word);
+    };
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn test_send_sync() {
+    assert_send_sync::<Position>();
+    assert_send_sync::<Token<TokenType>>();
+    assert_send_sync::<Node<String>>();
+    assert_send_sync::<CompileError>();
+    assert_send_sync::<GreenNode<TokenType>>();
+    assert_send_sync::<Document<TokenType, String>>();
+}
+
+#[test]
+fn test_threads() {
+    let files = ["one two", "three\nfour five", "six"];
+    let tokens = std::thread::scope(|scope| {
+        let handles = files.map(|x| scope.spawn(move || TokenType::tokenize(Lext::from_source("thread".into(), x))));
+        handles.map(|x| x.join().unwrap())
+    });
+
+    assert_eq!(tokens.iter().map(|x| x.len()).collect::<Vec<_>>(), [2, 3, 1]);
+    assert_eq!(tokens[1][1].position.text(), "four");
+    assert_eq!(tokens[1][1].position.0.ln(), 2);
+}
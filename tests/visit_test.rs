@@ -0,0 +1,127 @@
+use flexar::{prelude::*, visit::{self, Fold, Visitor, VisitorMut, Walk}};
+
+flexar::ast! {
+    #[derive(Debug, Clone)]
+    pub enum Expr {
+        Plus(Box<Node<Expr>>, Box<Node<Expr>>),
+        Call { name: String, args: Vec<Node<Expr>> },
+        Neg(Option<Box<Node<Expr>>>),
+        Int(u32),
+        Nothing,
+    }
+}
+
+flexar::ast! {
+    #[derive(Debug)]
+    pub struct Program {
+        pub name: String,
+        pub body: Box<[Node<Expr>]>,
+    }
+}
+
+fn node(idx: usize, expr: Expr) -> Node<Expr> {
+    Node::new(Position::new_oneline("visit", "abcdefghij", Some((idx, idx))), expr)
+}
+
+fn int(idx: usize, x: u32) -> Box<Node<Expr>> {
+    Box::new(node(idx, Expr::Int(x)))
+}
+
+/// `f(1 + 2, -(3)) + 4`
+fn tree() -> Node<Expr> {
+    let call = Expr::Call { name: "f".into(), args: vec![
+        node(2, Expr::Plus(int(3, 1), int(4, 2))),
+        node(5, Expr::Neg(Some(int(6, 3)))),
+    ] };
+    node(1, Expr::Plus(Box::new(node(1, call)), int(7, 4)))
+}
+
+#[derive(Default)]
+struct Collect(Vec<(usize, u32)>, usize);
+
+impl Visitor for Collect {
+    fn enter<N: Walk>(&mut self, node: &N, position: Position) -> bool {
+        if let Some(Expr::Int(x)) = visit::downcast_ref(node) { self.0.push((position.0.ln_idx(), *x)) }
+        !matches!(visit::downcast_ref(node), Some(Expr::Neg(_)))
+    }
+
+    fn leave<N: Walk>(&mut self, _: &N, _: Position) {
+        self.1 += 1;
+    }
+}
+
+struct Double;
+
+impl VisitorMut for Double {
+    fn leave<N: Walk>(&mut self, node: &mut N, _: Position) {
+        if let Some(Expr::Int(x)) = visit::downcast_mut(node) { *x *= 2 }
+    }
+}
+
+struct ConstantFold;
+
+impl Fold for ConstantFold {
+    fn fold<N: Walk>(&mut self, node: Node<N>) -> Node<N> {
+        let node = node.fold_children(self);
+        let Node { position, node } = node;
+        match visit::downcast::<N, Expr>(node) {
+            Ok(Expr::Plus(a, b)) => match (&a.node, &b.node) {
+                (Expr::Int(a), Expr::Int(b)) => Node::new(position, visit::downcast(Expr::Int(a + b)).unwrap()),
+                _ => Node::new(position, visit::downcast(Expr::Plus(a, b)).unwrap()),
+            },
+            Ok(x) => Node::new(position, visit::downcast(x).unwrap()),
+            Err(x) => Node::new(position, x),
+        }
+    }
+}
+
+#[test]
+fn test_visitor() {
+    let mut collect = Collect::default();
+    tree().visit_with(&mut collect);
+    assert_eq!(collect.0, [(3, 1), (4, 2), (7, 4)]); // `-(3)` is skipped
+    assert_eq!(collect.1, 7);
+
+    let program = Program { name: "main".into(), body: Box::new([tree(), node(9, Expr::Nothing)]) };
+    let mut collect = Collect::default();
+    program.walk(&mut collect);
+    assert_eq!(collect.1, 8);
+}
+
+#[test]
+fn test_visitor_mut() {
+    let mut tree = tree();
+    tree.visit_mut_with(&mut Double);
+    let mut collect = Collect::default();
+    tree.visit_with(&mut collect);
+    assert_eq!(collect.0, [(3, 2), (4, 4), (7, 8)]);
+}
+
+#[test]
+fn test_fold() {
+    let folded = tree().fold_with(&mut ConstantFold);
+    let Expr::Plus(call, four) = &folded.node else { panic!("expected plus, found {folded:?}") };
+    assert!(matches!(four.node, Expr::Int(4)));
+    let Expr::Call { name, args } = &call.node else { panic!("expected call, found {call:?}") };
+    assert_eq!(name, "f");
+    assert!(matches!((args[0].position.0.ln_idx(), &args[0].node), (2, Expr::Int(3))));
+    assert!(matches!(&args[1].node, Expr::Neg(Some(x)) if matches!(x.node, Expr::Int(3))));
+}
+
+#[test]
+fn test_tree() {
+    assert_eq!(tree().tree().to_string(), [
+        "Plus",
+        "├─ Call name: \"f\"",
+        "│  ├─ Plus",
+        "│  │  ├─ Int 1",
+        "│  │  └─ Int 2",
+        "│  └─ Neg",
+        "│     └─ Int 3",
+        "└─ Int 4",
+    ].join("\n"));
+
+    assert_eq!(tree().tree().sexpr().to_string(), "(Plus (Call name: \"f\" (Plus (Int 1) (Int 2)) (Neg (Int 3))) (Int 4))");
+    assert_eq!(node(9, Expr::Nothing).tree().positions(true).to_string(), "Nothing @ 1:9-1:9");
+    assert_eq!(int(3, 1).tree().sexpr().positions(true).to_string(), "(Int 1 @ 1:3-1:3)");
+}